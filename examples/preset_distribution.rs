@@ -0,0 +1,31 @@
+//! Prints how likely each preset is to be picked at a given difficulty.
+//!
+//! Usage: `cargo run --example preset_distribution -- [difficulty] [presets.toml]`
+//! where difficulty goes from 0 (start of a run) to 1 (top speed).
+
+use d2j2025::preset_library::PresetLibrary;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let difficulty: f32 = match args.next() {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            eprintln!("invalid difficulty '{}', expected a number", value);
+            std::process::exit(1);
+        }),
+        None => 0.0,
+    };
+
+    let path = args.next().unwrap_or_else(|| "presets.toml".to_owned());
+
+    let library = PresetLibrary::load(&path).unwrap_or_else(|error| {
+        eprintln!("could not load presets '{}': {}", path, error);
+        std::process::exit(1);
+    });
+
+    println!("difficulty {:.2}", difficulty);
+
+    for (name, probability) in library.distribution(difficulty) {
+        println!("{:<24} {:>6.2}%", name, probability * 100.0);
+    }
+}
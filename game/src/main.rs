@@ -0,0 +1,1268 @@
+use std::{collections::HashMap, rc::Rc};
+
+use d2j2025::{
+    Block, Crossing, DEFAULT_LEVERS, DeathCause, Gate, Letter, MAX_LEVERS, MIN_LEVERS, Rail,
+    RailRef, State, World,
+    game::{
+        CAR_LENGTH, Game, GameEvent, Inputs, LOCOMOTIVE_LENGTH, LeverSettings, LeverState,
+        LeverTargeting, MAX_CARS, TRAIN_WIDTH, TrainSettings, UpcomingFork, VISIBLE_BLOCKS,
+    },
+    preset_library::PresetLibrary,
+    replay::{Replay, ReplayError},
+    track::{BlockId, BlockMap},
+    track_art::block_art,
+};
+use macroquad::{
+    audio::*,
+    miniquad::{BlendFactor, BlendState, BlendValue, Equation},
+    prelude::*,
+};
+
+const CRT_FRAGMENT_SHADER: &str = r#"#version 100
+precision lowp float;
+
+varying vec4 color;
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform float iTime;
+
+// https://www.shadertoy.com/view/XtlSD7
+
+vec2 CRTCurveUV(vec2 uv)
+{
+    uv = uv * 2.0 - 1.0;
+    vec2 offset = abs( uv.yx ) / vec2( 6.0, 4.0 );
+    uv = uv + uv * offset * offset;
+    uv = uv * 0.5 + 0.5;
+    return uv;
+}
+
+void DrawVignette( inout vec3 color, vec2 uv )
+{
+    float vignette = uv.x * uv.y * ( 1.0 - uv.x ) * ( 1.0 - uv.y );
+    vignette = clamp( pow( 8.0 * vignette, 1.0 ), 0.0, 1.0 );
+    color *= vignette * 1.2;
+}
+
+
+void DrawScanline( inout vec3 color, vec2 uv )
+{
+    float scanline 	= clamp( 0.95 + 0.55 * cos( 3.14 * ( uv.y + 0.008 * mod(iTime, 1000.0) ) * 240.0 * 1.0 ), 0.0, 1.0 );
+    float grille 	= 0.85 + 0.15 * clamp( 1.5 * cos( 3.14 * uv.x * 640.0 * 1.0 ), 0.0, 1.0 );
+    color *= scanline * grille * 1.2;
+}
+
+void main() {
+    vec2 crtUV = CRTCurveUV(uv);
+    vec3 res = texture2D(Texture, uv).rgb * color.rgb;
+    if (crtUV.x < 0.0 || crtUV.x > 1.0 || crtUV.y < 0.0 || crtUV.y > 1.0)
+    {
+        res = vec3(0.0, 0.0, 0.0);
+    }
+    DrawVignette(res, crtUV);
+    DrawScanline(res, uv);
+    gl_FragColor = vec4(res, 1.0) * 2.2;
+
+}
+"#;
+
+const CRT_VERTEX_SHADER: &str = "#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+uniform float iTime;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+";
+
+const TRACK_VERTEX_SHADER: &str = "#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+";
+
+/// Fades track meshes by their block's `alpha`.
+const TRACK_FRAGMENT_SHADER: &str = "#version 100
+precision lowp float;
+
+varying vec4 color;
+varying vec2 uv;
+
+uniform float alpha;
+
+void main() {
+    gl_FragColor = vec4(color.rgb, color.a * alpha);
+}
+";
+
+/// Blocks fade out with their distance from the train, in blocks.
+fn block_alpha(distance: Option<usize>) -> f32 {
+    distance.map_or(0.0, |distance| {
+        (1.0 - distance.min(VISIBLE_BLOCKS) as f32 / VISIBLE_BLOCKS as f32).clamp(0.0, 1.0)
+    })
+}
+
+/// Debug view of `rail`: a line along `curve`, its smooth path in world
+/// space, with a dot on every track point.
+fn draw_rail(
+    distance: Option<usize>,
+    rail: &Rail,
+    curve: &[Vec2],
+    rail_color: Color,
+    is_fork: bool,
+    gate: Option<Gate>,
+) {
+    let mut rail_color = rail_color;
+    let points = &rail.points;
+    let is_wall = rail.is_wall;
+
+    for (i, point) in points.iter().enumerate() {
+        let point_world_position = rail.position + *point;
+
+        let mut color = if i == 0 {
+            if is_fork {
+                YELLOW // First point is yellow
+            } else {
+                WHITE // First point is white
+            }
+        } else if i == points.len() - 1 && is_wall {
+            RED // Last point is red
+        } else if i == points.len() - 1
+            && let Some(gate) = gate
+        {
+            if gate.open {
+                GREEN // Open gates are green
+            } else {
+                RED // Closed gates are red, like walls
+            }
+        } else {
+            BLUE // Middle points are blue
+        };
+
+        let alpha = block_alpha(distance);
+
+        color.a = alpha;
+        rail_color.a = alpha;
+
+        draw_circle(point_world_position.x, point_world_position.y, 5.0, color);
+    }
+
+    for pair in curve.windows(2) {
+        draw_line(pair[0].x, pair[0].y, pair[1].x, pair[1].y, 5.0, rail_color);
+    }
+}
+
+/// Debug view of a block: every rail drawn with `draw_rail`, selected fork
+/// branches and open spurs in blue.
+fn draw_block_debug(world: &World, id: BlockId, block: &Block, distance: Option<usize>) {
+    match block {
+        Block::Rail(rail) => {
+            let curve = world.rail_curve(RailRef {
+                block: id,
+                branch: 0,
+            });
+
+            draw_rail(distance, rail, &curve, BLUE, false, None);
+        }
+        Block::Fork(fork) => {
+            for (branch_idx, branch) in fork.branches.iter().enumerate() {
+                let color = if branch_idx == fork.selected {
+                    BLUE
+                } else {
+                    GRAY.with_alpha(0.75)
+                };
+
+                let curve = world.rail_curve(RailRef {
+                    block: id,
+                    branch: branch_idx,
+                });
+
+                draw_rail(distance, branch, &curve, color, true, None);
+            }
+        }
+        Block::Merge(merge) => {
+            let curve = world.rail_curve(RailRef {
+                block: id,
+                branch: 0,
+            });
+
+            draw_rail(distance, &merge.rail, &curve, BLUE, false, None);
+
+            let spur_color = if merge.gate.is_none_or(|gate| gate.open) {
+                BLUE
+            } else {
+                GRAY.with_alpha(0.75)
+            };
+
+            let curve = world.rail_curve(RailRef {
+                block: id,
+                branch: 1,
+            });
+
+            draw_rail(distance, &merge.spur, &curve, spur_color, false, merge.gate);
+        }
+    }
+}
+
+/// A block's track art as meshes, with the part of the world they cover.
+struct BlockMesh {
+    meshes: Vec<Mesh>,
+    bounds: Rect,
+}
+
+impl BlockMesh {
+    fn new(world: &World, id: BlockId) -> Self {
+        let art = block_art(world, id);
+
+        Self {
+            meshes: art.to_meshes(),
+            bounds: art.bounds(),
+        }
+    }
+
+    fn shift(&mut self, offset: Vec2) {
+        for vertex in self.meshes.iter_mut().flat_map(|mesh| &mut mesh.vertices) {
+            vertex.position += offset.extend(0.0);
+        }
+
+        self.bounds = self.bounds.offset(offset);
+    }
+}
+
+/// Track art meshes by block. A block's mesh is built when the block is
+/// generated, and only rebuilt when the game reports it changed, e.g. when
+/// its fork is switched.
+#[derive(Default)]
+struct TrackMeshes {
+    meshes: HashMap<BlockId, BlockMesh>,
+}
+
+impl TrackMeshes {
+    /// Builds the mesh of every block on the track from scratch.
+    fn rebuild(&mut self, world: &World) {
+        self.meshes = world
+            .track
+            .iter()
+            .map(|(id, _)| (id, BlockMesh::new(world, id)))
+            .collect();
+    }
+
+    /// Rebuilds the meshes of the `changed` blocks, and forgets the meshes
+    /// of blocks trimmed off the track.
+    fn update(&mut self, world: &World, changed: &[BlockId]) {
+        self.meshes.retain(|id, _| world.track.contains(*id));
+
+        for &id in changed.iter().filter(|id| world.track.contains(**id)) {
+            self.meshes.insert(id, BlockMesh::new(world, id));
+        }
+    }
+
+    fn shift(&mut self, offset: Vec2) {
+        for mesh in self.meshes.values_mut() {
+            mesh.shift(offset);
+        }
+    }
+}
+
+/// The part of the world `camera` shows.
+fn camera_view(camera: &Camera2D) -> Rect {
+    let half_size = (1.0 / camera.zoom).abs();
+    let corner = camera.target - half_size;
+
+    Rect::new(corner.x, corner.y, half_size.x * 2.0, half_size.y * 2.0)
+}
+
+/// Marks a level crossing with an orange cross, faded like the nearer of the
+/// two blocks crossing there.
+fn draw_crossing(distances: &BlockMap<usize>, crossing: &Crossing) {
+    let alpha = crossing
+        .blocks
+        .iter()
+        .map(|block| block_alpha(distances.get(block).copied()))
+        .fold(0.0, f32::max);
+    let color = ORANGE.with_alpha(alpha);
+    let size = 12.0;
+
+    for arm in [vec2(size, size), vec2(size, -size)] {
+        let start = crossing.position - arm;
+        let end = crossing.position + arm;
+
+        draw_line(start.x, start.y, end.x, end.y, 4.0, color);
+    }
+
+    draw_circle_lines(
+        crossing.position.x,
+        crossing.position.y,
+        size * 1.6,
+        3.0,
+        color,
+    );
+}
+
+/// Labels a fork or gate with its lever's letter, unless it is out of `view`
+/// or faded out.
+fn draw_rail_letter(
+    font: &Font,
+    view: Rect,
+    distance: Option<usize>,
+    position: Vec2,
+    letter: Letter,
+) {
+    let alpha = block_alpha(distance);
+    let label = Rect::new(position.x, position.y - 70.0, 40.0, 70.0);
+
+    if alpha <= 0.0 || !label.overlaps(&view) {
+        return;
+    }
+
+    draw_text_ex(
+        letter.name(),
+        position.x + 0.0,
+        position.y - 25.0,
+        TextParams {
+            font: Some(font),
+            font_size: 45,
+            font_scale: 1.0,
+            color: Color::new(1.00, 1.00, 1.00, alpha),
+            ..Default::default()
+        },
+    );
+}
+
+/// Draws a texture at the given position with a uniform scale and rotation (in radians).
+///
+/// # Arguments
+/// * `texture` - A reference to the Texture to be drawn.
+/// * `position` - The top-left position where the texture will be drawn.
+/// * `scale` - A uniform scale factor to apply to the texture.
+/// * `rotation` - The rotation in radians.
+pub fn draw_texture_helper(
+    texture: &Texture2D,
+    position: Vec2,
+    scale: f32,
+    rotation: f32,
+    color: Color,
+) {
+    let dest_size = Some(Vec2::new(texture.width() * scale, texture.height() * scale));
+
+    draw_texture_ex(
+        texture,
+        position.x,
+        position.y,
+        color,
+        DrawTextureParams {
+            dest_size,
+            // Set pivot to the center of the scaled texture
+            pivot: Some(Vec2::new(
+                texture.width() * scale / 2.0,
+                texture.height() * scale / 2.0,
+            )),
+            rotation,
+            ..Default::default()
+        },
+    );
+}
+
+/// Moves `current` exponentially toward `target`.
+///
+/// - `speed` controls how fast the approach is (higher means faster).
+/// - `dt` is the time delta (typically from `get_frame_time()`).
+///
+/// The exponential smoothing factor is calculated as:
+/// t = 1.0 - exp(-speed * dt)
+///
+/// This value is then used in linear interpolation.
+fn exponential_approach_vec2(current: Vec2, target: Vec2, speed: f32, dt: f32) -> Vec2 {
+    // Calculate the interpolation factor
+    let t = 1.0 - (-speed * dt).exp();
+    // Lerp between current and target using the factor
+    current.lerp(target, t)
+}
+
+fn draw_text_centered(font: &Font, text: &str, y: f32, font_size: u16, color: Color) {
+    let dimensions = measure_text(text, Some(font), font_size, 1.0);
+
+    draw_text_ex(
+        text,
+        screen_width() / 2.0 - dimensions.width / 2.0,
+        y,
+        TextParams {
+            font: Some(font),
+            font_size,
+            font_scale: 1.0,
+            color,
+            ..Default::default()
+        },
+    );
+}
+
+/// Where the lever at `index` is drawn, for levers drawn at `scale` from a
+/// texture `texture_height` high. Three levers fill the bottom of the screen;
+/// more are shrunk to fit, keeping their bottom edge in place.
+fn lever_position(index: usize, scale: f32, texture_height: f32) -> Vec2 {
+    vec2(
+        110.0 + index as f32 * 400.0 * scale,
+        500.0 + texture_height * 0.75 * (1.0 - scale),
+    )
+}
+
+fn draw_stat(font: &Font, label: &str, value: &str, center: Vec2, scale: f32) {
+    let label_size = (24.0 * scale) as u16;
+    let value_size = (37.0 * scale) as u16;
+    let label_dimensions = measure_text(label, Some(font), label_size, 1.0);
+    let value_dimensions = measure_text(value, Some(font), value_size, 1.0);
+
+    draw_text_ex(
+        label,
+        center.x - label_dimensions.width / 2.0,
+        center.y,
+        TextParams {
+            font: Some(font),
+            font_size: label_size,
+            font_scale: 1.0,
+            color: GRAY,
+            ..Default::default()
+        },
+    );
+
+    draw_text_ex(
+        value,
+        center.x - value_dimensions.width / 2.0,
+        center.y + 36.0 * scale,
+        TextParams {
+            font: Some(font),
+            font_size: value_size,
+            font_scale: 1.0,
+            color: WHITE,
+            ..Default::default()
+        },
+    );
+}
+
+/// Draws the run stats in the gaps after the first two levers, shrunk along
+/// with the levers so they stay clear of them.
+fn draw_run_stats(
+    font: &Font,
+    state: &State,
+    ms_to_next_point: f32,
+    lever_scale: f32,
+    lever_height: f32,
+) {
+    let survival_seconds = state.survival_ms / 1000.0;
+    let stat = |lever: usize, row: f32| {
+        lever_position(lever, lever_scale, lever_height) + vec2(331.0, row) * lever_scale
+    };
+
+    draw_stat(
+        font,
+        "TIME",
+        &format!("{:.1}s", survival_seconds),
+        stat(0, 80.0),
+        lever_scale,
+    );
+    draw_stat(
+        font,
+        "DISTANCE",
+        &format!("{} / {}", state.points_traversed, state.rails_traversed),
+        stat(0, 150.0),
+        lever_scale,
+    );
+    draw_stat(
+        font,
+        "FORKS",
+        &state.forks_passed.to_string(),
+        stat(1, 80.0),
+        lever_scale,
+    );
+    draw_stat(
+        font,
+        "SPEED",
+        &format!("{:.0} u/s", state.current_speed(ms_to_next_point)),
+        stat(1, 150.0),
+        lever_scale,
+    );
+}
+
+/// How many upcoming forks the HUD lists.
+const UPCOMING_FORKS_SHOWN: usize = 5;
+
+/// Lists the next forks on the train's route, with a marker on the ones the
+/// levers currently switch.
+fn draw_upcoming_forks(font: &Font, upcoming: &[UpcomingFork]) {
+    let text = |text: &str, x: f32, y: f32, color: Color| {
+        draw_text_ex(
+            text,
+            x,
+            y,
+            TextParams {
+                font: Some(font),
+                font_size: 24,
+                font_scale: 1.0,
+                color,
+                ..Default::default()
+            },
+        );
+    };
+
+    text("NEXT FORKS", 1010.0, 32.0, GRAY);
+
+    for (i, fork) in upcoming.iter().enumerate() {
+        let y = 62.0 + i as f32 * 28.0;
+        let color = match (fork.to_wall, fork.targeted) {
+            (true, true) => Color::new(1.0, 0.45, 0.45, 1.0),
+            (true, false) => Color::new(1.0, 0.45, 0.45, 0.5),
+            (false, true) => WHITE,
+            (false, false) => GRAY,
+        };
+
+        if fork.targeted {
+            text(">", 990.0, y, color);
+        }
+
+        text(fork.letter.name(), 1010.0, y, color);
+        text(&format!("{} pt", fork.distance), 1045.0, y, color);
+
+        if fork.to_wall {
+            text("WALL", 1120.0, y, color);
+        }
+    }
+}
+
+fn draw_game_over(font: &Font, cause: Option<DeathCause>) {
+    draw_rectangle(
+        0.0,
+        0.0,
+        screen_width(),
+        screen_height(),
+        Color::new(0.0, 0.0, 0.0, 0.6),
+    );
+
+    draw_text_centered(font, "CRASHED!", 300.0, 120, RED);
+
+    if let Some(cause) = cause {
+        draw_text_centered(font, cause.description(), 360.0, 45, ORANGE);
+    }
+
+    draw_text_centered(font, "PRESS ENTER TO RESTART", 430.0, 45, WHITE);
+}
+
+/// How long the train takes to tumble off the rail after a crash, and how
+/// long the screen shakes.
+const CRASH_SECONDS: f32 = 0.8;
+const SHAKE_SECONDS: f32 = 0.5;
+const SHAKE_AMPLITUDE: f32 = 14.0;
+const CRASH_PARTICLES: usize = 48;
+
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    color: Color,
+}
+
+/// The crash sequence: the train tumbles off the rail, debris bursts out of
+/// the locomotive and the screen shakes. Purely visual, so it runs on frame
+/// time rather than simulation ticks.
+struct CrashEffect {
+    elapsed: f32,
+    particles: Vec<Particle>,
+}
+
+impl CrashEffect {
+    fn new(position: Vec2) -> Self {
+        let colors = [ORANGE, YELLOW, GRAY];
+        let particles = (0..CRASH_PARTICLES)
+            .map(|i| {
+                let angle = macroquad::rand::gen_range(0.0, std::f32::consts::TAU);
+                let speed = macroquad::rand::gen_range(150.0, 450.0);
+
+                Particle {
+                    position,
+                    velocity: Vec2::from_angle(angle) * speed,
+                    color: colors[i % colors.len()],
+                }
+            })
+            .collect();
+
+        Self {
+            elapsed: 0.0,
+            particles,
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+
+        for particle in &mut self.particles {
+            particle.position += particle.velocity * dt;
+            particle.velocity *= (1.0 - 3.0 * dt).max(0.0);
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= CRASH_SECONDS
+    }
+
+    /// How far the train has tumbled, easing from 0 at the crash to 1 once
+    /// it lies still.
+    fn tumble(&self) -> f32 {
+        let t = (self.elapsed / CRASH_SECONDS).min(1.0);
+        1.0 - (1.0 - t).powi(2)
+    }
+
+    fn camera_shake(&self) -> Vec2 {
+        if self.elapsed >= SHAKE_SECONDS {
+            return Vec2::ZERO;
+        }
+
+        let direction = vec2(
+            macroquad::rand::gen_range(-1.0, 1.0),
+            macroquad::rand::gen_range(-1.0, 1.0),
+        );
+
+        direction * SHAKE_AMPLITUDE * (1.0 - self.elapsed / SHAKE_SECONDS)
+    }
+
+    /// Pushes a piece of the train off the rail, towards `side` (1 or -1),
+    /// so that alternating sides make the cars jackknife.
+    fn displace(&self, (position, rotation): (Vec2, f32), side: f32) -> (Vec2, f32) {
+        let tumble = self.tumble();
+        let normal = Vec2::from_angle(rotation).perp();
+
+        (
+            position + normal * side * 25.0 * tumble,
+            rotation + side * 0.9 * tumble,
+        )
+    }
+
+    fn draw_particles(&self) {
+        let alpha = 1.0 - (self.elapsed / (CRASH_SECONDS * 1.5)).min(1.0);
+
+        for particle in &self.particles {
+            draw_circle(
+                particle.position.x,
+                particle.position.y,
+                4.0,
+                particle.color.with_alpha(alpha),
+            );
+        }
+    }
+}
+
+/// A short burst of decaying rumble, as a 16-bit mono WAV file. There is no
+/// crash recording among the assets, so it is synthesized at startup.
+fn crash_sound_wav() -> Vec<u8> {
+    const SAMPLE_RATE: u32 = 22050;
+
+    let samples = (SAMPLE_RATE as f32 * 0.7) as u32;
+    let mut noise: u32 = 0x1234_5678;
+    let mut rumble = 0.0;
+    let mut data = Vec::with_capacity(samples as usize * 2);
+
+    for i in 0..samples {
+        // Xorshift white noise, low-passed into a rumble.
+        noise ^= noise << 13;
+        noise ^= noise >> 17;
+        noise ^= noise << 5;
+
+        let white = noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+        rumble += (white - rumble) * 0.25;
+
+        let envelope = (-6.0 * i as f32 / SAMPLE_RATE as f32).exp();
+        let sample = (rumble * envelope * 0.9 * i16::MAX as f32) as i16;
+
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    let mut wav = Vec::with_capacity(44 + data.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // Bytes per second
+    wav.extend_from_slice(&2u16.to_le_bytes()); // Bytes per sample
+    wav.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    wav.extend(data);
+
+    wav
+}
+
+fn draw_seed(font: &Font, seed: u64, is_replay: bool) {
+    let text = if is_replay {
+        format!("SEED {} - REPLAY", seed)
+    } else {
+        format!("SEED {}", seed)
+    };
+
+    draw_text_ex(
+        &text,
+        100.0,
+        32.0,
+        TextParams {
+            font: Some(font),
+            font_size: 24,
+            font_scale: 1.0,
+            color: GRAY,
+            ..Default::default()
+        },
+    );
+}
+
+#[derive(Default)]
+struct Args {
+    seed: Option<u64>,
+    replay: Option<String>,
+    presets: Option<String>,
+    levers: Option<usize>,
+    max_levers: Option<usize>,
+    targeting: Option<LeverTargeting>,
+    cars: Option<usize>,
+    max_cars: Option<usize>,
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// Reads `--seed <n>`, `--replay <path>`, `--presets <path>`, `--levers <n>`,
+/// `--max-levers <n>`, `--targeting <rule>`, `--cars <n>` and
+/// `--max-cars <n>`, also accepting the `--flag=value` spelling.
+fn parse_args() -> Args {
+    let mut parsed = Args::default();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+            None => (arg, None),
+        };
+
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .unwrap_or_else(|| exit_with_error(&format!("{} expects a value", flag)))
+        };
+
+        match flag.as_str() {
+            "--seed" => {
+                let value = value();
+                parsed.seed = Some(value.parse().unwrap_or_else(|_| {
+                    exit_with_error(&format!(
+                        "invalid seed '{}', expected an unsigned integer",
+                        value
+                    ))
+                }));
+            }
+            "--replay" => parsed.replay = Some(value()),
+            "--presets" => parsed.presets = Some(value()),
+            "--levers" | "--max-levers" => {
+                let value = value();
+                let count = value
+                    .parse()
+                    .ok()
+                    .filter(|count| (MIN_LEVERS..=MAX_LEVERS).contains(count))
+                    .unwrap_or_else(|| {
+                        exit_with_error(&format!(
+                            "invalid lever count '{}', expected {} to {}",
+                            value, MIN_LEVERS, MAX_LEVERS
+                        ))
+                    });
+
+                if flag == "--levers" {
+                    parsed.levers = Some(count);
+                } else {
+                    parsed.max_levers = Some(count);
+                }
+            }
+            "--cars" | "--max-cars" => {
+                let value = value();
+                let count = value
+                    .parse()
+                    .ok()
+                    .filter(|&count| count <= MAX_CARS)
+                    .unwrap_or_else(|| {
+                        exit_with_error(&format!(
+                            "invalid car count '{}', expected 0 to {}",
+                            value, MAX_CARS
+                        ))
+                    });
+
+                if flag == "--cars" {
+                    parsed.cars = Some(count);
+                } else {
+                    parsed.max_cars = Some(count);
+                }
+            }
+            "--targeting" => {
+                let value = value();
+                parsed.targeting = Some(LeverTargeting::from_name(&value).unwrap_or_else(|| {
+                    exit_with_error(&format!(
+                        "invalid lever targeting '{}', expected next, all, nearest or sticky",
+                        value
+                    ))
+                }));
+            }
+            _ => exit_with_error(&format!("unknown argument '{}'", flag)),
+        }
+    }
+
+    let levers = parsed.levers.unwrap_or(DEFAULT_LEVERS);
+
+    if let Some(max_levers) = parsed.max_levers
+        && max_levers < levers
+    {
+        exit_with_error(&format!(
+            "--max-levers {} is below the {} levers a run starts with",
+            max_levers, levers
+        ));
+    }
+
+    parsed
+}
+
+/// Saves the replay of a finished run under `replays/`, returning its path.
+fn save_replay(replay: &Replay) -> Result<String, ReplayError> {
+    std::fs::create_dir_all("replays")?;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let path = format!("replays/{}-{}.replay", timestamp, replay.seed);
+    replay.save(&path)?;
+
+    Ok(path)
+}
+
+fn window_conf() -> Conf {
+    Conf {
+        window_title: "Railway Panic".to_owned(),
+        fullscreen: false,
+        window_width: 1280,
+        window_height: 720,
+        ..Default::default()
+    }
+}
+
+#[macroquad::main(window_conf())]
+async fn main() {
+    let crt_render_target = render_target(1280, 720);
+
+    let texture = load_texture("overlay.png").await.unwrap();
+    let font = load_ttf_font("BebasNeue-Regular.ttf").await.unwrap();
+    let sound_horn = load_sound("horn.ogg").await.unwrap();
+    let sound_switch = load_sound("switch.ogg").await.unwrap();
+    let sound_step = load_sound("step.ogg").await.unwrap();
+    let sound_crash = load_sound_from_bytes(&crash_sound_wav()).await.unwrap();
+    let lever0_texture = load_texture("lever0.png").await.unwrap();
+    let lever1_texture = load_texture("lever1.png").await.unwrap();
+    let player_texture = load_texture("player.png").await.unwrap();
+
+    let crt_material = load_material(
+        ShaderSource::Glsl {
+            vertex: CRT_VERTEX_SHADER,
+            fragment: CRT_FRAGMENT_SHADER,
+        },
+        MaterialParams {
+            uniforms: vec![UniformDesc::new("iTime", UniformType::Float1)],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let track_material = load_material(
+        ShaderSource::Glsl {
+            vertex: TRACK_VERTEX_SHADER,
+            fragment: TRACK_FRAGMENT_SHADER,
+        },
+        MaterialParams {
+            uniforms: vec![UniformDesc::new("alpha", UniformType::Float1)],
+            pipeline_params: PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let args = parse_args();
+
+    let presets_path = args.presets.as_deref().unwrap_or("presets.toml");
+    let presets = PresetLibrary::load(presets_path).unwrap_or_else(|error| {
+        exit_with_error(&format!(
+            "could not load presets '{}': {}",
+            presets_path, error
+        ))
+    });
+
+    let start_levers = args.levers.unwrap_or(DEFAULT_LEVERS);
+    let levers = LeverSettings::new(start_levers, args.max_levers.unwrap_or(start_levers))
+        .with_targeting(args.targeting.unwrap_or_default());
+    let start_cars = args.cars.unwrap_or(TrainSettings::default().cars);
+    let train = TrainSettings::new(
+        start_cars,
+        args.max_cars.unwrap_or(TrainSettings::default().max_cars),
+    );
+
+    // Replays keep the lever and train settings they were recorded with.
+    let mut game = match (&args.replay, args.seed) {
+        (Some(path), _) => match Replay::load(path) {
+            Ok(replay) if replay.presets != presets.hash => exit_with_error(&format!(
+                "replay '{}' was recorded with different presets than '{}'",
+                path, presets_path
+            )),
+            Ok(replay) => Game::from_replay(replay),
+            Err(error) => exit_with_error(&format!("could not load replay '{}': {}", path, error)),
+        },
+        (None, Some(seed)) => Game::with_seed(seed).with_levers(levers).with_train(train),
+        (None, None) => Game::new().with_levers(levers).with_train(train),
+    }
+    .with_presets(Rc::new(presets));
+
+    let mut camera_pos = vec2(0.0, 0.0);
+    let mut crash_effect: Option<CrashEffect> = None;
+    let mut debug_view = false;
+    let mut track_meshes = TrackMeshes::default();
+    track_meshes.rebuild(&game.world);
+
+    loop {
+        clear_background(BLACK);
+
+        let inputs = Inputs {
+            select_left: is_key_pressed(KeyCode::Left),
+            select_right: is_key_pressed(KeyCode::Right),
+            pull_lever: is_key_pressed(KeyCode::Space),
+            restart: is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::R),
+        };
+
+        if is_key_pressed(KeyCode::F3) {
+            debug_view = !debug_view;
+        }
+
+        for event in game.step(get_frame_time(), inputs) {
+            match event {
+                GameEvent::SelectorMoved => play_sound(&sound_step, PlaySoundParams::default()),
+                GameEvent::LeverPulled => play_sound(&sound_switch, PlaySoundParams::default()),
+                GameEvent::SpedUp => play_sound(&sound_horn, PlaySoundParams::default()),
+                GameEvent::LeverAdded => play_sound(&sound_switch, PlaySoundParams::default()),
+                GameEvent::Crashed => {
+                    crash_effect = Some(CrashEffect::new(game.train_transform().0));
+                    play_sound(&sound_crash, PlaySoundParams::default());
+
+                    if let Some(playback) = &game.playback {
+                        let recorded = (playback.replay.crash_tick, playback.replay.crash_cause);
+                        let replayed = (game.recording.crash_tick, game.recording.crash_cause);
+
+                        if recorded != replayed {
+                            eprintln!(
+                                "replay diverged: recorded crash {:?}, replayed crash {:?}",
+                                recorded, replayed
+                            );
+                        }
+                    } else {
+                        match save_replay(&game.recording) {
+                            Ok(path) => println!("replay saved to {}", path),
+                            Err(error) => eprintln!("could not save replay: {}", error),
+                        }
+                    }
+                }
+                GameEvent::OriginShifted => {
+                    camera_pos += game.origin_shift;
+                    track_meshes.shift(game.origin_shift);
+                }
+                GameEvent::Restarted => {
+                    camera_pos = vec2(0.0, 0.0);
+                    crash_effect = None;
+                    track_meshes.rebuild(&game.world);
+                }
+            }
+        }
+
+        track_meshes.update(&game.world, &game.changed_blocks);
+
+        let Game {
+            world,
+            state,
+            ms_to_next_point,
+            selected_index,
+            ..
+        } = &game;
+        let ms_to_next_point = *ms_to_next_point;
+        let selected_index = *selected_index;
+
+        let (train_position, train_rotation) = game.train_transform();
+
+        crt_material.set_uniform("iTime", (get_time() * 1000.0) as f32);
+
+        camera_pos = exponential_approach_vec2(camera_pos, train_position, 5.0, get_frame_time());
+
+        if let Some(effect) = &mut crash_effect {
+            effect.update(get_frame_time());
+        }
+
+        let shake = crash_effect
+            .as_ref()
+            .map_or(Vec2::ZERO, CrashEffect::camera_shake);
+
+        let zoom_level = 0.0019 * ms_to_next_point.remap(100.0, 30.0, 1.0, 0.75);
+
+        let aspect_ratio = screen_width() / screen_height();
+        let world_camera = Camera2D {
+            zoom: vec2(zoom_level / aspect_ratio, zoom_level),
+            target: camera_pos + vec2(0.0, 100.0) + shake,
+            render_target: Some(crt_render_target.clone()),
+            ..Default::default()
+        };
+        macroquad::camera::set_camera(&world_camera);
+        let view = camera_view(&world_camera);
+
+        clear_background(Color::new(0.1, 0.1, 0.1, 1.0));
+
+        let distances = state.block_distances(world);
+
+        if debug_view {
+            for (id, block) in world.track.iter() {
+                draw_block_debug(world, id, block, distances.get(&id).copied());
+            }
+        } else {
+            gl_use_material(&track_material);
+
+            for (id, _) in world.track.iter() {
+                let Some(block_mesh) = track_meshes.meshes.get(&id) else {
+                    continue;
+                };
+                let alpha = block_alpha(distances.get(&id).copied());
+
+                if alpha > 0.0 && block_mesh.bounds.overlaps(&view) {
+                    track_material.set_uniform("alpha", alpha);
+
+                    for mesh in &block_mesh.meshes {
+                        draw_mesh(mesh);
+                    }
+                }
+            }
+
+            gl_use_default_material();
+        }
+
+        for crossing in &game.crossings {
+            draw_crossing(&distances, crossing);
+        }
+
+        // After a crash, the locomotive and its cars tumble off the rail to
+        // alternating sides.
+        let displace = |transform: (Vec2, f32), side: f32| match &crash_effect {
+            Some(effect) => effect.displace(transform, side),
+            None => transform,
+        };
+
+        for (i, car) in game.car_transforms().into_iter().enumerate() {
+            let side = if i % 2 == 0 { -1.0 } else { 1.0 };
+            let (car_position, car_rotation) = displace(car, side);
+
+            draw_rectangle_ex(
+                car_position.x,
+                car_position.y,
+                CAR_LENGTH,
+                TRAIN_WIDTH,
+                DrawRectangleParams {
+                    offset: vec2(0.5, 0.5),
+                    rotation: car_rotation,
+                    color: DARKGREEN,
+                },
+            );
+        }
+
+        let (train_position, train_rotation) = displace((train_position, train_rotation), 1.0);
+
+        draw_rectangle_ex(
+            train_position.x,
+            train_position.y,
+            LOCOMOTIVE_LENGTH,
+            TRAIN_WIDTH,
+            DrawRectangleParams {
+                offset: vec2(0.5, 0.5),
+                rotation: train_rotation,
+                color: GREEN,
+            },
+        );
+
+        if let Some(effect) = &crash_effect {
+            effect.draw_particles();
+        }
+
+        for (id, block) in world.track.iter() {
+            let distance = distances.get(&id).copied();
+
+            match block {
+                Block::Rail(_) => {}
+                Block::Fork(fork) => {
+                    // Every branch starts at the same point, so one letter is enough.
+                    let branch = &fork.branches[0];
+
+                    draw_rail_letter(
+                        &font,
+                        view,
+                        distance,
+                        branch.position + branch.points[0],
+                        fork.letter,
+                    );
+                }
+                Block::Merge(merge) => {
+                    if let Some(gate) = merge.gate {
+                        let spur = &merge.spur;
+
+                        draw_rail_letter(
+                            &font,
+                            view,
+                            distance,
+                            spur.position + *spur.points.last().unwrap(),
+                            gate.letter,
+                        );
+                    }
+                }
+            }
+        }
+
+        macroquad::camera::set_camera(&Camera2D {
+            zoom: vec2(0.0025 / aspect_ratio, 0.0025) * 1.15,
+            target: vec2(screen_width() / 2.0, screen_height() / 2.0),
+            render_target: None,
+            ..Default::default()
+        });
+
+        gl_use_material(&crt_material);
+        draw_texture_ex(
+            &crt_render_target.texture,
+            0.,
+            0.,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                ..Default::default()
+            },
+        );
+        gl_use_default_material();
+
+        draw_texture(&texture, 0.0, 0.0, WHITE);
+
+        let lever_states = game.lever_states();
+        let lever_scale = 3.0 / lever_states.len().max(3) as f32;
+
+        for (i, &lever_state) in lever_states.iter().enumerate() {
+            let (lever, color, label) = match lever_state {
+                LeverState::NoForkAhead => (false, Color::new(1.0, 1.0, 1.0, 0.35), "NO FORK"),
+                LeverState::Set {
+                    pulled,
+                    to_wall: true,
+                } => (pulled, Color::new(1.0, 0.45, 0.45, 1.0), "WALL"),
+                LeverState::Set { pulled, .. } => (pulled, WHITE, ""),
+            };
+
+            let position = lever_position(i, lever_scale, lever0_texture.height());
+            let texture = if lever {
+                &lever0_texture
+            } else {
+                &lever1_texture
+            };
+
+            if selected_index == i {
+                let x_offset = if lever { -60.0 } else { 60.0 };
+
+                draw_texture_helper(
+                    &player_texture,
+                    position + vec2(80.0 + x_offset, -100.0) * lever_scale,
+                    0.75 * lever_scale,
+                    0.0,
+                    WHITE,
+                );
+            }
+
+            draw_texture_helper(texture, position, 0.75 * lever_scale, 0.0, color);
+
+            draw_text_ex(
+                label,
+                position.x + 100.0 * lever_scale,
+                position.y - 10.0 * lever_scale,
+                TextParams {
+                    font: Some(&font),
+                    font_size: (30.0 * lever_scale) as u16,
+                    font_scale: 1.0,
+                    color,
+                    ..Default::default()
+                },
+            );
+
+            draw_text_ex(
+                Letter::from_index(i).name(),
+                position.x + 136.0 * lever_scale,
+                position.y + 194.0 * lever_scale,
+                TextParams {
+                    font: Some(&font),
+                    font_size: (37.0 * lever_scale) as u16,
+                    font_scale: 1.0,
+                    color: Color::new(0.00, 0.00, 0.00, 1.00),
+                    ..Default::default()
+                },
+            );
+        }
+
+        draw_run_stats(
+            &font,
+            state,
+            ms_to_next_point,
+            lever_scale,
+            lever0_texture.height(),
+        );
+        draw_seed(&font, game.seed(), game.is_replay());
+        draw_upcoming_forks(&font, &game.upcoming_forks(UPCOMING_FORKS_SHOWN));
+
+        // The game over screen waits for the crash sequence to play out.
+        if !state.alive && crash_effect.as_ref().is_none_or(CrashEffect::is_finished) {
+            draw_game_over(&font, state.death_cause);
+        }
+
+        next_frame().await
+    }
+}
+
+// TODO:
+// - release exe
+// - cleanup code
+// - credits: Vittorio Romeo, Marco Ieni, Sonia Misericordia
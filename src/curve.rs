@@ -0,0 +1,131 @@
+use macroquad::math::Vec2;
+
+use crate::Angle;
+
+/// Straight pieces each curve segment is measured and drawn with.
+pub const CURVE_SAMPLES: usize = 8;
+
+/// The smooth curve between two consecutive track points: a Catmull-Rom
+/// spline, shaped by the points before and after them so that consecutive
+/// segments meet without a kink.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CurveSegment {
+    pub before: Vec2,
+    pub start: Vec2,
+    pub end: Vec2,
+    pub after: Vec2,
+}
+
+impl CurveSegment {
+    /// Builds the segment from `start` to `end`. Where the track has no
+    /// point before or after them, e.g. at a wall, it is continued in a
+    /// straight line.
+    pub fn new(before: Option<Vec2>, start: Vec2, end: Vec2, after: Option<Vec2>) -> Self {
+        Self {
+            before: before.unwrap_or(2.0 * start - end),
+            start,
+            end,
+            after: after.unwrap_or(2.0 * end - start),
+        }
+    }
+
+    /// The point `t` of the way from `start` to `end`, by curve parameter.
+    pub fn position(&self, t: f32) -> Vec2 {
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        0.5 * (2.0 * self.start
+            + (self.end - self.before) * t
+            + (2.0 * self.before - 5.0 * self.start + 4.0 * self.end - self.after) * t2
+            + (3.0 * self.start - self.before - 3.0 * self.end + self.after) * t3)
+    }
+
+    /// Direction of travel at curve parameter `t`, not normalized.
+    pub fn tangent(&self, t: f32) -> Vec2 {
+        let t2 = t * t;
+
+        0.5 * ((self.end - self.before)
+            + 2.0 * (2.0 * self.before - 5.0 * self.start + 4.0 * self.end - self.after) * t
+            + 3.0 * (3.0 * self.start - self.before - 3.0 * self.end + self.after) * t2)
+    }
+
+    /// `CURVE_SAMPLES + 1` points along the segment, `start` and `end`
+    /// included.
+    pub fn samples(&self) -> impl Iterator<Item = Vec2> + '_ {
+        (0..=CURVE_SAMPLES).map(|i| self.position(i as f32 / CURVE_SAMPLES as f32))
+    }
+
+    /// Length of the segment along the curve. A segment from a point to
+    /// itself, like the one at the shared last point of a rail, has none.
+    pub fn length(&self) -> f32 {
+        if self.start == self.end {
+            return 0.0;
+        }
+
+        let mut samples = self.samples();
+        let mut previous = samples.next().unwrap();
+
+        samples
+            .map(|sample| {
+                let length = previous.distance(sample);
+                previous = sample;
+                length
+            })
+            .sum()
+    }
+
+    /// Position and heading `distance` along the curve from `start`, clamped
+    /// to the segment. Distances are measured along the same straight pieces
+    /// as `length`, so moving by a distance covers exactly that much ground.
+    pub fn at_distance(&self, distance: f32) -> (Vec2, Angle) {
+        let mut remaining = distance.max(0.0);
+        let mut previous = self.start;
+
+        for i in 1..=CURVE_SAMPLES {
+            let t = i as f32 / CURVE_SAMPLES as f32;
+            let sample = self.position(t);
+            let length = previous.distance(sample);
+
+            if remaining <= length || i == CURVE_SAMPLES {
+                let fraction = if length > 0.0 {
+                    (remaining / length).min(1.0)
+                } else {
+                    0.0
+                };
+                let tangent = self.tangent(t - (1.0 - fraction) / CURVE_SAMPLES as f32);
+                let heading = if tangent.length() > 0.0 {
+                    tangent
+                } else {
+                    sample - previous
+                };
+
+                return (previous.lerp(sample, fraction), heading.to_angle());
+            }
+
+            remaining -= length;
+            previous = sample;
+        }
+
+        unreachable!()
+    }
+}
+
+/// A smooth curve through `points`, as `CURVE_SAMPLES` straight pieces per
+/// pair of points. `before` and `after` are the track points on either side,
+/// if any.
+pub fn smooth_points(points: &[Vec2], before: Option<Vec2>, after: Option<Vec2>) -> Vec<Vec2> {
+    let mut smooth = vec![points[0]];
+
+    for i in 0..points.len() - 1 {
+        let segment = CurveSegment::new(
+            if i == 0 { before } else { Some(points[i - 1]) },
+            points[i],
+            points[i + 1],
+            points.get(i + 2).copied().or(after),
+        );
+
+        smooth.extend(segment.samples().skip(1));
+    }
+
+    smooth
+}
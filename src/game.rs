@@ -0,0 +1,1124 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    iter,
+    rc::Rc,
+};
+
+use macroquad::math::Vec2;
+
+use crate::{
+    Angle, Block, BlockCurves, Crossing, DEFAULT_LEVERS, DeathCause, Hazard, Letter, MAX_LEVERS,
+    MIN_LEVERS, State, World,
+    curve::smooth_points,
+    curve_crossings, preset,
+    preset_library::{PresetLibrary, STARTING_PRESET},
+    replay::{Playback, Replay},
+    track::BlockId,
+};
+
+/// Length of a fixed simulation step. Point intervals and the speed-up period
+/// are multiples of it, so train motion does not depend on the frame rate.
+pub const TICK_MS: f32 = 5.0;
+
+/// Time between track points at the start of a run, and at top speed.
+pub const START_MS_TO_NEXT_POINT: f32 = 100.0;
+pub const MIN_MS_TO_NEXT_POINT: f32 = 30.0;
+
+/// Time between speed-ups, each taking `SPEEDUP_MS` off the time between
+/// track points until it reaches `MIN_MS_TO_NEXT_POINT`.
+const SPEEDUP_PERIOD_MS: f32 = 10000.0;
+const SPEEDUP_MS: f32 = 10.0;
+
+/// Distance between track points on most rails. The train runs this far
+/// every `Game::ms_to_next_point`, along the curve through the points.
+pub const POINT_SPACING: f32 = 30.0;
+
+/// How many blocks of track are kept generated ahead of the train, on every
+/// branch it could still take.
+const LOOKAHEAD_BLOCKS: usize = 24;
+
+/// How many blocks the train has already passed are kept, so they can fade
+/// out behind it.
+const PATH_HISTORY_BLOCKS: usize = 16;
+
+/// Most branches generated ahead of the train at once. Splitting the track
+/// further would multiply the blocks to generate ahead.
+const MAX_OPEN_ENDS: usize = 2;
+
+/// Speed-ups between two added levers, while a run has fewer than
+/// `LeverSettings::max`.
+const SPEEDUPS_PER_LEVER: usize = 2;
+
+/// Blocks this many away from the train, or further, are faded out.
+pub const VISIBLE_BLOCKS: usize = 10;
+
+/// Once the train is this far from the world origin, the whole world is
+/// shifted back towards it, so `f32` positions keep their precision however
+/// long a run lasts.
+pub const ORIGIN_REBASE_DISTANCE: f32 = 4096.0;
+
+/// World positions are only ever shifted by multiples of this. It is a power
+/// of two no finer than the precision of any position in play, so positions
+/// shifted towards the origin, like the train's, lose nothing.
+const ORIGIN_GRID: f32 = 1024.0;
+
+/// Which forks and gates a lever pull switches.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LeverTargeting {
+    /// The next one with the lever's letter on the train's route.
+    #[default]
+    Next,
+    /// Every visible one with the lever's letter, on any branch.
+    All,
+    /// The next one on the train's route, whatever its letter.
+    Nearest,
+    /// Every lever keeps a position, and every one with its letter follows
+    /// it, including track generated later.
+    Sticky,
+}
+
+impl LeverTargeting {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LeverTargeting::Next => "next",
+            LeverTargeting::All => "all",
+            LeverTargeting::Nearest => "nearest",
+            LeverTargeting::Sticky => "sticky",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "next" => Some(LeverTargeting::Next),
+            "all" => Some(LeverTargeting::All),
+            "nearest" => Some(LeverTargeting::Nearest),
+            "sticky" => Some(LeverTargeting::Sticky),
+            _ => None,
+        }
+    }
+}
+
+/// How many levers a run starts with, how many it can grow to as the train
+/// speeds up, and what they switch.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LeverSettings {
+    pub start: usize,
+    pub max: usize,
+    pub targeting: LeverTargeting,
+}
+
+impl Default for LeverSettings {
+    fn default() -> Self {
+        Self {
+            start: DEFAULT_LEVERS,
+            max: DEFAULT_LEVERS,
+            targeting: LeverTargeting::default(),
+        }
+    }
+}
+
+impl LeverSettings {
+    /// Clamps both counts to `MIN_LEVERS..=MAX_LEVERS`, with `max` no lower
+    /// than `start`.
+    pub fn new(start: usize, max: usize) -> Self {
+        let start = start.clamp(MIN_LEVERS, MAX_LEVERS);
+
+        Self {
+            start,
+            max: max.clamp(start, MAX_LEVERS),
+            targeting: LeverTargeting::default(),
+        }
+    }
+
+    pub fn with_targeting(self, targeting: LeverTargeting) -> Self {
+        Self { targeting, ..self }
+    }
+}
+
+/// Player inputs collected during a single frame.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Inputs {
+    pub select_left: bool,
+    pub select_right: bool,
+    pub pull_lever: bool,
+    pub restart: bool,
+}
+
+impl Inputs {
+    fn merge(self, other: Inputs) -> Inputs {
+        Inputs {
+            select_left: self.select_left || other.select_left,
+            select_right: self.select_right || other.select_right,
+            pull_lever: self.pull_lever || other.pull_lever,
+            restart: self.restart || other.restart,
+        }
+    }
+}
+
+/// What a lever shows. Levers have no state of their own: they mirror the
+/// next fork or gate with their letter on the train's route.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LeverState {
+    /// Nothing with the lever's letter is ahead of the train.
+    NoForkAhead,
+    /// The next fork or gate with the lever's letter. `pulled` is whether it
+    /// was switched since it was generated, and `to_wall` whether it
+    /// currently stops the train.
+    Set { pulled: bool, to_wall: bool },
+}
+
+/// Most cars a train can pull.
+pub const MAX_CARS: usize = 16;
+
+/// Track points the train runs between two added cars, while it has fewer
+/// than `TrainSettings::max_cars`.
+const POINTS_PER_CAR: u32 = 400;
+
+/// Sizes of the locomotive and its cars, in world units.
+pub const LOCOMOTIVE_LENGTH: f32 = 80.0;
+pub const CAR_LENGTH: f32 = 60.0;
+pub const CAR_GAP: f32 = 10.0;
+pub const TRAIN_WIDTH: f32 = 40.0;
+
+/// How many cars the locomotive pulls at the start of a run, and how many
+/// the train can grow to as the score grows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TrainSettings {
+    pub cars: usize,
+    pub max_cars: usize,
+}
+
+impl Default for TrainSettings {
+    fn default() -> Self {
+        Self {
+            cars: 2,
+            max_cars: 6,
+        }
+    }
+}
+
+impl TrainSettings {
+    /// Clamps both counts to `0..=MAX_CARS`, with `max_cars` no lower than
+    /// `cars`.
+    pub fn new(cars: usize, max_cars: usize) -> Self {
+        let cars = cars.min(MAX_CARS);
+
+        Self {
+            cars,
+            max_cars: max_cars.clamp(cars, MAX_CARS),
+        }
+    }
+}
+
+/// A fork or gate on the train's route, as listed on the HUD.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UpcomingFork {
+    pub block: BlockId,
+    pub letter: Letter,
+    /// Track points the train has left to run before entering the block.
+    pub distance: usize,
+    /// Whether the block currently stops the train.
+    pub to_wall: bool,
+    /// Whether pulling the letter's lever switches this block, which is the
+    /// case for the first one of each letter.
+    pub targeted: bool,
+}
+
+/// Things that happened during a `Game::step`, for the frontend to react to
+/// (e.g. by playing sounds).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameEvent {
+    SelectorMoved,
+    LeverPulled,
+    SpedUp,
+    LeverAdded,
+    /// Every world position moved by `Game::origin_shift`.
+    OriginShifted,
+    Crashed,
+    Restarted,
+}
+
+/// Headless simulation of a run: owns the track and the train state, but
+/// never touches graphics or audio.
+pub struct Game {
+    pub world: World,
+    pub state: State,
+    pub ms_to_next_point: f32,
+    pub selected_index: usize,
+    /// Levers in play, from `levers.start` up to `levers.max`.
+    pub lever_count: usize,
+    /// How many times each lever was pulled, which sets the position of
+    /// every fork with its letter under `LeverTargeting::Sticky`.
+    pub lever_pulls: Vec<usize>,
+    pub levers: LeverSettings,
+    pub train: TrainSettings,
+    /// Where the track crosses itself, on the curves the train runs along.
+    pub crossings: Vec<Crossing>,
+    /// The rail curves of every block, which `crossings` are found on. A
+    /// block's curves only change when it is generated, when a block next
+    /// to it is generated or trimmed, or when the fork after it switches.
+    pub rail_curves: BTreeMap<BlockId, BlockCurves>,
+    /// Number of times the train has sped up this run.
+    pub speedups: usize,
+    /// How far every world position moved during the last `step`, so that
+    /// anything kept outside the game can follow.
+    pub origin_shift: Vec2,
+    /// Blocks generated during the last `step`, or whose curves, fork or gate
+    /// changed, so that art kept outside the game can follow.
+    pub changed_blocks: Vec<BlockId>,
+    /// Frame time not yet consumed by a whole tick.
+    pub accumulator_ms: f32,
+    /// Number of ticks simulated since the run started.
+    pub ticks: u64,
+    /// Inputs received since the last tick, applied at the start of the next.
+    pub pending_inputs: Inputs,
+    /// Seed to reuse on restart; when `None`, every run gets a fresh seed.
+    pub fixed_seed: Option<u64>,
+    /// Inputs of the current run, with the ticks they were applied on.
+    pub recording: Replay,
+    /// When set, inputs come from this replay instead of the player.
+    pub playback: Option<Playback>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn new_world(seed: u64, presets: Rc<PresetLibrary>, letter_count: usize) -> World {
+    let mut world = World::new(seed, presets);
+    world.letter_count = letter_count;
+
+    preset::spawn_preset(&mut world, STARTING_PRESET);
+    preset::spawn_preset(&mut world, STARTING_PRESET);
+    preset::spawn_preset(&mut world, STARTING_PRESET);
+    preset::spawn_preset(&mut world, STARTING_PRESET);
+
+    world
+}
+
+impl Game {
+    /// Starts a run on a random track.
+    pub fn new() -> Self {
+        Self::start(
+            rand::random(),
+            None,
+            Rc::new(PresetLibrary::builtin()),
+            LeverSettings::default(),
+            TrainSettings::default(),
+        )
+    }
+
+    /// Starts a run on the track generated by `seed`, and keeps that track
+    /// across restarts.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::start(
+            seed,
+            Some(seed),
+            Rc::new(PresetLibrary::builtin()),
+            LeverSettings::default(),
+            TrainSettings::default(),
+        )
+    }
+
+    /// Plays back a recorded run, reproducing it exactly.
+    pub fn from_replay(replay: Replay) -> Self {
+        Self {
+            playback: Some(Playback::new(replay.clone())),
+            ..Self::with_seed(replay.seed)
+                .with_levers(replay.levers)
+                .with_train(replay.train)
+        }
+    }
+
+    /// Rebuilds the track from `presets` instead of the built-in ones. Meant
+    /// to be called before the run starts; restarts keep using `presets`.
+    pub fn with_presets(self, presets: Rc<PresetLibrary>) -> Self {
+        Self {
+            playback: self.playback,
+            ..Self::start(
+                self.world.seed,
+                self.fixed_seed,
+                presets,
+                self.levers,
+                self.train,
+            )
+        }
+    }
+
+    /// Rebuilds the run with `levers` instead of the default lever count.
+    /// Meant to be called before the run starts; restarts keep using `levers`.
+    pub fn with_levers(self, levers: LeverSettings) -> Self {
+        Self {
+            playback: self.playback,
+            ..Self::start(
+                self.world.seed,
+                self.fixed_seed,
+                self.world.presets.clone(),
+                levers,
+                self.train,
+            )
+        }
+    }
+
+    /// Rebuilds the run with `train` instead of the default train length.
+    /// Meant to be called before the run starts; restarts keep using `train`.
+    pub fn with_train(self, train: TrainSettings) -> Self {
+        Self {
+            playback: self.playback,
+            ..Self::start(
+                self.world.seed,
+                self.fixed_seed,
+                self.world.presets.clone(),
+                self.levers,
+                train,
+            )
+        }
+    }
+
+    fn start(
+        seed: u64,
+        fixed_seed: Option<u64>,
+        presets: Rc<PresetLibrary>,
+        levers: LeverSettings,
+        train: TrainSettings,
+    ) -> Self {
+        let mut game = Self {
+            recording: Replay::new(seed, presets.hash, levers, train),
+            world: new_world(seed, presets, levers.start),
+            state: State::new(),
+            ms_to_next_point: START_MS_TO_NEXT_POINT,
+            selected_index: 0,
+            lever_count: levers.start,
+            lever_pulls: vec![0; levers.start],
+            levers,
+            train,
+            crossings: Vec::new(),
+            rail_curves: BTreeMap::new(),
+            speedups: 0,
+            origin_shift: Vec2::ZERO,
+            changed_blocks: Vec::new(),
+            accumulator_ms: 0.0,
+            ticks: 0,
+            pending_inputs: Inputs::default(),
+            fixed_seed,
+            playback: None,
+        };
+
+        // Track growth only happens on ticks afterwards, so the generated
+        // track does not depend on how time is split into frames.
+        game.grow_and_trim_world();
+        game.state.record_trail_point(&game.world);
+        game.skip_zero_length_segments();
+
+        game
+    }
+
+    fn restarted(&self) -> Self {
+        let seed = self.fixed_seed.unwrap_or_else(rand::random);
+
+        Self {
+            playback: self
+                .playback
+                .as_ref()
+                .map(|playback| Playback::new(playback.replay.clone())),
+            ..Self::start(
+                seed,
+                self.fixed_seed,
+                self.world.presets.clone(),
+                self.levers,
+                self.train,
+            )
+        }
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.world.seed
+    }
+
+    /// How far the train is into its speed-up schedule, from 0 at the start
+    /// of a run to 1 at top speed.
+    pub fn difficulty(&self) -> f32 {
+        ((START_MS_TO_NEXT_POINT - self.ms_to_next_point)
+            / (START_MS_TO_NEXT_POINT - MIN_MS_TO_NEXT_POINT))
+            .clamp(0.0, 1.0)
+    }
+
+    /// The next fork or gate showing `letter` on the train's route.
+    pub fn next_fork_index(&self, letter: Letter) -> Option<BlockId> {
+        let next = self.state.get_current_rail(&self.world).next()?;
+        self.world.find_next_fork_index(next.block, letter)
+    }
+
+    /// The forks and gates ahead of the train that pulling the lever of
+    /// `letter` switches, nearest first.
+    pub fn lever_targets(&self, letter: Letter) -> Vec<BlockId> {
+        match self.levers.targeting {
+            LeverTargeting::Next => self.next_fork_index(letter).into_iter().collect(),
+            LeverTargeting::Nearest => self
+                .forks_on_route(1)
+                .iter()
+                .map(|fork| fork.block)
+                .collect(),
+            LeverTargeting::All => self.forks_ahead(letter, VISIBLE_BLOCKS),
+            LeverTargeting::Sticky => self.forks_ahead(letter, usize::MAX),
+        }
+    }
+
+    /// The forks and gates showing `letter` fewer than `max_distance` blocks
+    /// ahead of the train, on any branch, nearest first. The block the train
+    /// is on is left alone.
+    fn forks_ahead(&self, letter: Letter, max_distance: usize) -> Vec<BlockId> {
+        let distances = self.world.distances_from(self.state.current_rail_idx());
+        let mut forks: Vec<BlockId> = self
+            .world
+            .track
+            .iter()
+            .filter(|&(id, block)| {
+                distances
+                    .get(&id)
+                    .is_some_and(|&distance| distance > 0 && distance < max_distance)
+                    && block.letter() == Some(letter)
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        forks.sort_by_key(|id| distances[id]);
+        forks
+    }
+
+    /// The next `count` forks and gates on the train's route, nearest first.
+    /// The route ends at the first wall, or where the track is not generated
+    /// yet.
+    pub fn upcoming_forks(&self, count: usize) -> Vec<UpcomingFork> {
+        let mut upcoming = self.forks_on_route(count);
+        let targets: Vec<BlockId> = Letter::first(self.lever_count)
+            .into_iter()
+            .flat_map(|letter| self.lever_targets(letter))
+            .collect();
+
+        for fork in &mut upcoming {
+            fork.targeted = targets.contains(&fork.block);
+        }
+
+        upcoming
+    }
+
+    /// `upcoming_forks`, without working out which ones are targeted.
+    fn forks_on_route(&self, count: usize) -> Vec<UpcomingFork> {
+        let mut upcoming: Vec<UpcomingFork> = Vec::new();
+
+        // The next block starts at the last point of the current rail, see
+        // `World::rail_curve`.
+        let current_rail = self.state.get_current_entered_rail(&self.world);
+        let mut distance =
+            (current_rail.points.len() - 1).saturating_sub(self.state.current_point_idx);
+        let mut next = self.state.get_current_rail(&self.world).next();
+
+        while let Some(rail) = next
+            && upcoming.len() < count
+        {
+            let block = &self.world.track[rail.block];
+
+            if let Some(letter) = block.letter() {
+                upcoming.push(UpcomingFork {
+                    block: rail.block,
+                    letter,
+                    distance,
+                    to_wall: block.is_set_to_wall(),
+                    targeted: false,
+                });
+            }
+
+            distance += block.entered_rail(rail.branch).points.len() - 1;
+            next = block.next();
+        }
+
+        upcoming
+    }
+
+    /// What the lever of `letter` shows: the nearest block it switches.
+    pub fn lever_state(&self, letter: Letter) -> LeverState {
+        match self.lever_targets(letter).first().copied() {
+            Some(id) => LeverState::Set {
+                pulled: self.world.track[id].is_switched(),
+                to_wall: self.world.track[id].is_set_to_wall(),
+            },
+            None => LeverState::NoForkAhead,
+        }
+    }
+
+    /// What every lever in play shows, from left to right.
+    pub fn lever_states(&self) -> Vec<LeverState> {
+        Letter::first(self.lever_count)
+            .into_iter()
+            .map(|letter| self.lever_state(letter))
+            .collect()
+    }
+
+    /// How many cars the locomotive pulls: one more every `POINTS_PER_CAR`
+    /// points, up to `TrainSettings::max_cars`.
+    pub fn car_count(&self) -> usize {
+        let earned = (self.state.points_traversed / POINTS_PER_CAR) as usize;
+
+        (self.train.cars + earned).min(self.train.max_cars)
+    }
+
+    /// How far the train reaches behind the centre of the locomotive.
+    pub fn train_length(&self) -> f32 {
+        LOCOMOTIVE_LENGTH / 2.0 + self.car_count() as f32 * (CAR_GAP + CAR_LENGTH)
+    }
+
+    /// Whether the locomotive has just reached a crossing that the rest of
+    /// the train is still on.
+    fn crosses_own_body(&self) -> bool {
+        if self.crossings.is_empty() {
+            return false;
+        }
+
+        let segment = self.state.current_segment(&self.world);
+        // Only whole ticks count, so crashes do not depend on frame timing.
+        let travelled = self.state.segment_distance.min(segment.length());
+        let body_length = self.train_length() - travelled;
+
+        // The curve is never shorter than the straight line, so crossings
+        // further away cannot have been reached yet.
+        let nearby: Vec<&Crossing> = self
+            .crossings
+            .iter()
+            .filter(|crossing| {
+                crossing.position.distance(segment.start) <= travelled + CROSSING_TOLERANCE
+            })
+            .collect();
+
+        if nearby.is_empty() {
+            return false;
+        }
+
+        let samples: Vec<Vec2> = segment.samples().collect();
+
+        nearby.into_iter().any(|crossing| {
+            distance_along(&samples, crossing.position, travelled).is_some()
+                && distance_along(
+                    &trail_curve(&self.state.trail, segment.end, body_length),
+                    crossing.position,
+                    body_length,
+                )
+                .is_some()
+        })
+    }
+
+    pub fn selected_letter(&self) -> Letter {
+        Letter::from_index(self.selected_index)
+    }
+
+    /// Advances the simulation by `dt` seconds in fixed `TICK_MS` steps.
+    /// Time that does not fill a whole tick is carried over to the next call,
+    /// and `inputs` are applied at the start of the next tick. While playing
+    /// back a replay, only `inputs.restart` is used.
+    pub fn step(&mut self, dt: f32, inputs: Inputs) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        self.origin_shift = Vec2::ZERO;
+        self.changed_blocks.clear();
+
+        if !self.state.alive && inputs.restart {
+            *self = self.restarted();
+            events.push(GameEvent::Restarted);
+        }
+
+        if self.playback.is_none() {
+            self.pending_inputs = self.pending_inputs.merge(inputs);
+        }
+
+        self.accumulator_ms += dt * 1000.0;
+
+        while self.accumulator_ms >= TICK_MS {
+            self.accumulator_ms -= TICK_MS;
+            self.tick(&mut events);
+        }
+
+        self.changed_blocks.sort_unstable();
+        self.changed_blocks.dedup();
+
+        events
+    }
+
+    fn apply_inputs(&mut self, inputs: Inputs, events: &mut Vec<GameEvent>) {
+        if inputs.select_left {
+            if self.selected_index == 0 {
+                self.selected_index = self.lever_count - 1;
+            } else {
+                self.selected_index -= 1;
+            }
+
+            events.push(GameEvent::SelectorMoved);
+        } else if inputs.select_right {
+            self.selected_index += 1;
+            if self.selected_index >= self.lever_count {
+                self.selected_index = 0;
+            }
+
+            events.push(GameEvent::SelectorMoved);
+        }
+
+        if inputs.pull_lever {
+            let targets = self.lever_targets(self.selected_letter());
+
+            // Outside of sticky levers, a lever with nothing ahead to switch
+            // does not move.
+            if self.levers.targeting == LeverTargeting::Sticky {
+                self.lever_pulls[self.selected_index] += 1;
+                let switched = self.apply_sticky_levers();
+                self.blocks_switched(&switched);
+
+                events.push(GameEvent::LeverPulled);
+            } else if !targets.is_empty() {
+                for &id in &targets {
+                    self.world.track[id].switch();
+                }
+
+                self.blocks_switched(&targets);
+                events.push(GameEvent::LeverPulled);
+            }
+        }
+
+        self.recording.record(self.ticks, inputs);
+    }
+
+    /// Advances the simulation by exactly one `TICK_MS`.
+    fn tick(&mut self, events: &mut Vec<GameEvent>) {
+        let inputs = match &mut self.playback {
+            Some(playback) => playback.inputs_for_tick(self.ticks),
+            None => std::mem::take(&mut self.pending_inputs),
+        };
+
+        self.apply_inputs(inputs, events);
+
+        let was_alive = self.state.alive;
+        let rails_traversed = self.state.rails_traversed;
+
+        self.ticks += 1;
+        self.state.speedup_timer += TICK_MS;
+
+        if self.state.alive {
+            self.state.survival_ms += TICK_MS;
+        }
+
+        let speed = self.state.current_speed(self.ms_to_next_point);
+        self.state.advance(&self.world, speed * TICK_MS / 1000.0);
+
+        if self.state.alive
+            && self.state.speedup_timer >= SPEEDUP_PERIOD_MS
+            && self.ms_to_next_point > MIN_MS_TO_NEXT_POINT
+        {
+            self.ms_to_next_point -= SPEEDUP_MS;
+            self.state.speedup_timer = 0.0;
+            self.speedups += 1;
+
+            events.push(GameEvent::SpedUp);
+
+            if self.speedups.is_multiple_of(SPEEDUPS_PER_LEVER)
+                && self.lever_count < self.levers.max
+            {
+                self.add_lever();
+                events.push(GameEvent::LeverAdded);
+            }
+        }
+
+        self.skip_zero_length_segments();
+
+        // The track ahead only needs to change once the train enters a block.
+        if self.state.rails_traversed != rails_traversed {
+            self.grow_and_trim_world();
+            self.rebase_origin(events);
+        }
+
+        if self.state.alive && self.crosses_own_body() {
+            self.state.crash(DeathCause::Hazard(Hazard::OwnTrain));
+        }
+
+        if was_alive && !self.state.alive {
+            self.recording.crash_tick = Some(self.ticks);
+            self.recording.crash_cause = self.state.death_cause;
+            events.push(GameEvent::Crashed);
+        }
+    }
+
+    /// Adds a lever on the right. Only track generated from now on uses its
+    /// letter, so it first shows up at the far end of the lookahead.
+    fn add_lever(&mut self) {
+        self.lever_count += 1;
+        self.lever_pulls.push(0);
+        self.world.letter_count = self.lever_count;
+    }
+
+    /// Sets every fork and gate ahead of the train to the position of its
+    /// letter's lever, and returns the ones that moved.
+    fn apply_sticky_levers(&mut self) -> Vec<BlockId> {
+        let mut switched = Vec::new();
+
+        for letter in Letter::first(self.lever_count) {
+            for id in self.lever_targets(letter) {
+                let block = &mut self.world.track[id];
+                let before = setting(block);
+                block.set_pulls(self.lever_pulls[letter.index()]);
+
+                if setting(block) != before {
+                    switched.push(id);
+                }
+            }
+        }
+
+        switched
+    }
+
+    /// Updates the crossings after the forks and gates `switched` moved.
+    /// Only the rail leading into a fork bends with it.
+    fn blocks_switched(&mut self, switched: &[BlockId]) {
+        self.changed_blocks.extend(switched);
+
+        let changed: Vec<BlockId> = switched
+            .iter()
+            .filter(|&&id| matches!(self.world.track[id], Block::Fork(_)))
+            .filter_map(|&id| self.world.parent(id))
+            .map(|parent| parent.block)
+            .collect();
+
+        self.update_crossings(changed);
+    }
+
+    /// Recomputes the curves of the `changed` blocks, and every crossing
+    /// they take part in.
+    fn update_crossings(&mut self, mut changed: Vec<BlockId>) {
+        changed.sort_unstable();
+        changed.dedup();
+
+        for &id in &changed {
+            self.rail_curves.insert(id, self.world.block_curves(id));
+        }
+
+        self.changed_blocks.extend(&changed);
+
+        self.crossings
+            .retain(|crossing| !crossing.blocks.iter().any(|id| changed.contains(id)));
+
+        for &id in &changed {
+            let curves = &self.rail_curves[&id];
+
+            for (&other, other_curves) in &self.rail_curves {
+                // Pairs of changed blocks are only checked once.
+                if other < id && changed.contains(&other) {
+                    continue;
+                }
+
+                self.crossings
+                    .extend(curve_crossings(id, curves, other, other_curves));
+            }
+        }
+    }
+
+    fn grow_and_trim_world(&mut self) {
+        self.trim_world();
+
+        let difficulty = self.difficulty();
+        // Rails the new blocks attach to bend towards them.
+        let mut changed = Vec::new();
+
+        loop {
+            let current_idx = self.state.current_rail_idx();
+            let distances = self.world.distances_from(current_idx);
+            let open_ends = self.world.open_ends_from(current_idx);
+
+            let Some(&end) = open_ends
+                .iter()
+                .find(|end| distances[&end.block] < LOOKAHEAD_BLOCKS)
+            else {
+                break;
+            };
+
+            self.world.cursor = Some(end);
+            changed.push(end.block);
+            let ms_to_next_point = self.projected_ms_to_next_point();
+            preset::preset_random(
+                &mut self.world,
+                difficulty,
+                ms_to_next_point,
+                open_ends.len() < MAX_OPEN_ENDS,
+            );
+        }
+
+        changed.extend(
+            self.world
+                .track
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| !self.rail_curves.contains_key(id)),
+        );
+        self.update_crossings(changed);
+
+        // New forks follow sticky levers as soon as they are generated.
+        if self.levers.targeting == LeverTargeting::Sticky {
+            let switched = self.apply_sticky_levers();
+            self.blocks_switched(&switched);
+        }
+    }
+
+    /// Time between points once the train reaches forks generated at the
+    /// cursor, which lies a whole lookahead ahead of it. Counts the speed-ups
+    /// on the way to the cursor, and one more for the blocks a preset places
+    /// in front of its forks.
+    fn projected_ms_to_next_point(&self) -> f32 {
+        let current_idx = self.state.current_rail_idx();
+        let mut points = 0;
+        let mut rail = self.world.cursor;
+
+        // The whole current rail is counted, which only brings the
+        // speed-ups forward.
+        while let Some(next) = rail {
+            points += self.world.rail(next).points.len() - 1;
+
+            if next.block == current_idx {
+                break;
+            }
+
+            rail = self.world.parent(next.block);
+        }
+
+        let mut ms_to_next_point = self.ms_to_next_point;
+        let mut remaining_ms = SPEEDUP_PERIOD_MS - self.state.speedup_timer;
+        let mut travel_ms = points as f32 * ms_to_next_point;
+
+        while travel_ms >= remaining_ms && ms_to_next_point > MIN_MS_TO_NEXT_POINT {
+            // Points left after the speed-up go by faster.
+            let points_left = (travel_ms - remaining_ms) / ms_to_next_point;
+
+            ms_to_next_point -= SPEEDUP_MS;
+            travel_ms = points_left * ms_to_next_point;
+            remaining_ms = SPEEDUP_PERIOD_MS;
+        }
+
+        (ms_to_next_point - SPEEDUP_MS).max(MIN_MS_TO_NEXT_POINT)
+    }
+
+    /// Drops the oldest blocks behind the train, and every branch it can no
+    /// longer reach.
+    fn trim_world(&mut self) {
+        let excess = self.state.path.len().saturating_sub(PATH_HISTORY_BLOCKS);
+        self.state.path.drain(..excess);
+
+        let reachable = self.world.distances_from(self.state.current_rail_idx());
+        let path = &self.state.path;
+        let keep =
+            |id: BlockId| reachable.contains_key(&id) || path.iter().any(|entry| entry.block == id);
+
+        // Curves bend towards the blocks on either side, so the blocks next
+        // to the ones removed change shape.
+        let mut changed = Vec::new();
+
+        for (id, block) in self.world.track.iter() {
+            for next in block.rails().iter().filter_map(|rail| rail.next) {
+                match (keep(id), keep(next.block)) {
+                    (true, false) => changed.push(id),
+                    (false, true) => changed.push(next.block),
+                    _ => {}
+                }
+            }
+        }
+
+        self.world.retain_blocks(keep);
+
+        let track = &self.world.track;
+        self.rail_curves.retain(|id, _| track.contains(*id));
+        self.crossings
+            .retain(|crossing| crossing.blocks.iter().all(|id| track.contains(*id)));
+        self.update_crossings(changed);
+    }
+
+    /// Shifts the world back towards the origin once the train has run far
+    /// enough from it, along with everything that stores world positions.
+    fn rebase_origin(&mut self, events: &mut Vec<GameEvent>) {
+        let position = self.state.get_current_point_world_position(&self.world);
+
+        if position.length() < ORIGIN_REBASE_DISTANCE {
+            return;
+        }
+
+        let offset = -(position / ORIGIN_GRID).round() * ORIGIN_GRID;
+
+        self.world.shift_origin(offset);
+        self.state.shift_origin(offset);
+
+        for crossing in &mut self.crossings {
+            crossing.position += offset;
+        }
+
+        for curves in self.rail_curves.values_mut() {
+            curves.shift(offset);
+        }
+
+        self.origin_shift += offset;
+        events.push(GameEvent::OriginShifted);
+    }
+
+    /// Moves the train past boundary points shared by two rails (see
+    /// `World::rail_curve`), where it would sit on a zero-length segment for a
+    /// whole point interval.
+    fn skip_zero_length_segments(&mut self) {
+        while self.state.alive
+            && self.state.get_current_point_world_position(&self.world)
+                == self.state.get_next_point_world_position(&self.world)
+        {
+            self.state.move_to_next_point(&self.world);
+        }
+    }
+
+    /// Current train position and rotation on the curve through the track
+    /// points. Frame time not yet simulated is run ahead, so motion stays
+    /// smooth between ticks.
+    pub fn train_transform(&self) -> (Vec2, Angle) {
+        let mut ahead = self.state.clone();
+        let speed = ahead.current_speed(self.ms_to_next_point);
+        ahead.advance(&self.world, speed * self.accumulator_ms / 1000.0);
+
+        // A crashed train stays where it hit.
+        let segment = ahead.current_segment(&self.world);
+        let distance = if ahead.alive {
+            ahead.segment_distance
+        } else {
+            segment.length()
+        };
+
+        segment.at_distance(distance)
+    }
+
+    /// Position and rotation of every car behind the locomotive, front to
+    /// back. Cars follow the points the locomotive passed, so switching a
+    /// fork under them does not move them.
+    pub fn car_transforms(&self) -> Vec<(Vec2, Angle)> {
+        let (train_position, train_rotation) = self.train_transform();
+        let trail: Vec<Vec2> = iter::once(train_position)
+            .chain(self.state.trail.iter().rev().copied())
+            .collect();
+
+        (0..self.car_count())
+            .map(|i| {
+                let center = LOCOMOTIVE_LENGTH / 2.0
+                    + CAR_GAP
+                    + CAR_LENGTH / 2.0
+                    + i as f32 * (CAR_LENGTH + CAR_GAP);
+                let front = point_behind(&trail, center - CAR_LENGTH / 2.0, train_rotation);
+                let back = point_behind(&trail, center + CAR_LENGTH / 2.0, train_rotation);
+
+                ((front + back) / 2.0, (front - back).to_angle())
+            })
+            .collect()
+    }
+}
+
+/// How close two track points have to be to count as the same crossing.
+const CROSSING_TOLERANCE: f32 = 0.5;
+
+/// How far along `curve` it first passes within `CROSSING_TOLERANCE` of
+/// `position`, if it does within `max_distance` of its start.
+fn distance_along(curve: &[Vec2], position: Vec2, max_distance: f32) -> Option<f32> {
+    let mut travelled = 0.0;
+
+    for pair in curve.windows(2) {
+        if travelled > max_distance {
+            break;
+        }
+
+        let segment = pair[1] - pair[0];
+        let length = segment.length();
+
+        if length > 0.0 {
+            let offset = position - pair[0];
+            let along = offset.dot(segment) / length;
+
+            if (0.0..=length).contains(&along)
+                && offset.perp_dot(segment).abs() / length < CROSSING_TOLERANCE
+                && travelled + along <= max_distance
+            {
+                return Some(travelled + along);
+            }
+        }
+
+        travelled += length;
+    }
+
+    None
+}
+
+/// The curve the train ran along, back from the point the locomotive last
+/// reached through the newest `trail` points, far enough to cover `length`.
+/// `ahead` is the track point the locomotive is heading for.
+fn trail_curve(trail: &VecDeque<Vec2>, ahead: Vec2, length: f32) -> Vec<Vec2> {
+    let mut points: Vec<Vec2> = Vec::new();
+    let mut covered = 0.0;
+
+    for &point in trail.iter().rev() {
+        // Skips the copies of shared boundary points, see `World::rail_curve`.
+        if let Some(&last) = points.last() {
+            if point.distance(last) <= 1e-2 {
+                continue;
+            }
+
+            covered += point.distance(last);
+        }
+
+        points.push(point);
+
+        // One point past `length`, which shapes the end of the curve.
+        if covered > length {
+            break;
+        }
+    }
+
+    let after = if covered > length { points.pop() } else { None };
+
+    if points.len() < 2 {
+        return points;
+    }
+
+    smooth_points(&points, Some(ahead), after)
+}
+
+/// The point `distance` back along `trail`, which runs from the locomotive
+/// backwards. Past its end, e.g. at the start of a run, the trail is extended
+/// in a straight line, against `heading` if it has no length yet.
+fn point_behind(trail: &[Vec2], distance: f32, heading: Angle) -> Vec2 {
+    let mut remaining = distance;
+
+    for pair in trail.windows(2) {
+        let length = pair[0].distance(pair[1]);
+
+        if remaining <= length && length > 0.0 {
+            return pair[0].lerp(pair[1], remaining / length);
+        }
+
+        remaining -= length;
+    }
+
+    let direction = trail
+        .windows(2)
+        .rev()
+        .map(|pair| pair[1] - pair[0])
+        .find(|segment| segment.length() > 0.0)
+        .map_or(-Vec2::from_angle(heading), Vec2::normalize);
+
+    *trail.last().unwrap() + direction * remaining
+}
+
+/// Which way a fork or gate is set, to tell when it moves.
+fn setting(block: &Block) -> Option<usize> {
+    match block {
+        Block::Rail(_) => None,
+        Block::Fork(fork) => Some(fork.selected),
+        Block::Merge(merge) => merge.gate.map(|gate| usize::from(gate.open)),
+    }
+}
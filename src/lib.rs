@@ -0,0 +1,1098 @@
+pub mod curve;
+pub mod game;
+pub mod preset;
+pub mod preset_library;
+pub mod replay;
+pub mod track;
+pub mod track_art;
+
+use std::{collections::VecDeque, f32::consts::PI, rc::Rc};
+
+use curve::{CURVE_SAMPLES, CurveSegment, smooth_points};
+use macroquad::math::{Vec2, vec2};
+use preset_library::PresetLibrary;
+use rand::{SeedableRng, rngs::StdRng};
+use track::{BlockId, BlockMap, Track};
+
+pub type Angle = f32;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rail {
+    pub position: Vec2,
+    pub points: Vec<Vec2>,
+    pub is_wall: bool,
+    /// The rail this one leads into, once it has been generated.
+    pub next: Option<RailRef>,
+    /// The rail leading into this one, while it is part of the track. Every
+    /// branch of a fork is entered from the same rail.
+    pub prev: Option<RailRef>,
+}
+
+/// Identifies one rail of the track: a block, and the branch within it (the
+/// fork branch, or 1 for the spur of a merge, and 0 otherwise).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RailRef {
+    pub block: BlockId,
+    pub branch: usize,
+}
+
+/// Fewest and most levers a run can be played with.
+pub const MIN_LEVERS: usize = 2;
+pub const MAX_LEVERS: usize = 6;
+
+/// Levers a run has unless configured otherwise.
+pub const DEFAULT_LEVERS: usize = 3;
+
+/// Label of a lever, and of the forks and gates it controls.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Letter(u8);
+
+impl Letter {
+    pub const A: Letter = Letter(0);
+    pub const B: Letter = Letter(1);
+    pub const C: Letter = Letter(2);
+    pub const D: Letter = Letter(3);
+    pub const E: Letter = Letter(4);
+    pub const F: Letter = Letter(5);
+
+    const NAMES: [&'static str; MAX_LEVERS] = ["A", "B", "C", "D", "E", "F"];
+
+    pub fn from_index(index: usize) -> Self {
+        assert!(index < MAX_LEVERS, "there is no letter {}", index);
+        Letter(index as u8)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .position(|other| *other == name)
+            .map(Self::from_index)
+    }
+
+    /// Position of the letter's lever, from the left.
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES[self.index()]
+    }
+
+    /// The letters of the first `count` levers.
+    pub fn first(count: usize) -> Vec<Letter> {
+        (0..count).map(Self::from_index).collect()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fork {
+    /// Every branch leaving the fork, all starting at the same point.
+    pub branches: Vec<Rail>,
+    /// Index into `branches` of the branch the train will take.
+    pub selected: usize,
+    pub letter: Letter,
+}
+
+/// Letter-controlled gate where a spur joins the main line. A closed gate
+/// stops the spur like a wall.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Gate {
+    pub letter: Letter,
+    pub open: bool,
+}
+
+/// Where a spur that left the main line earlier joins it again. Both rails
+/// end at the same point, and the track continues from `rail`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Merge {
+    pub rail: Rail,
+    pub spur: Rail,
+    pub gate: Option<Gate>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Block {
+    Rail(Rail),
+    Fork(Fork),
+    Merge(Merge),
+}
+
+impl Block {
+    pub fn points(&self) -> &[Vec2] {
+        &self.active_rail().points
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.active_rail().position
+    }
+
+    /// The rail the train would run on, following fork selections. For
+    /// merges, this is the main line.
+    pub fn active_rail(&self) -> &Rail {
+        match self {
+            Block::Rail(rail) => rail,
+            Block::Fork(fork) => fork.selected_rail(),
+            Block::Merge(merge) => &merge.rail,
+        }
+    }
+
+    /// The rail the train runs on after entering through `branch`.
+    pub fn entered_rail(&self, branch: usize) -> &Rail {
+        match self {
+            Block::Merge(merge) if branch == 1 => &merge.spur,
+            _ => self.active_rail(),
+        }
+    }
+
+    /// Every rail of this block, including unselected fork branches and
+    /// merge spurs, indexed by branch.
+    pub fn rails(&self) -> Vec<&Rail> {
+        match self {
+            Block::Rail(rail) => vec![rail],
+            Block::Fork(fork) => fork.branches.iter().collect(),
+            Block::Merge(merge) => vec![&merge.rail, &merge.spur],
+        }
+    }
+
+    /// The rails the track continues from, indexed by branch.
+    pub fn exit_rails(&self) -> Vec<&Rail> {
+        match self {
+            Block::Rail(rail) => vec![rail],
+            Block::Fork(fork) => fork.branches.iter().collect(),
+            Block::Merge(merge) => vec![&merge.rail],
+        }
+    }
+
+    fn rails_mut(&mut self) -> Vec<&mut Rail> {
+        match self {
+            Block::Rail(rail) => vec![rail],
+            Block::Fork(fork) => fork.branches.iter_mut().collect(),
+            Block::Merge(merge) => vec![&mut merge.rail, &mut merge.spur],
+        }
+    }
+
+    /// The rail the train continues into, following fork selections.
+    pub fn next(&self) -> Option<RailRef> {
+        self.active_rail().next
+    }
+
+    /// The letter of the lever controlling this block, if any.
+    pub fn letter(&self) -> Option<Letter> {
+        match self {
+            Block::Rail(_) => None,
+            Block::Fork(fork) => Some(fork.letter),
+            Block::Merge(merge) => merge.gate.map(|gate| gate.letter),
+        }
+    }
+
+    /// Whether the block's lever has been pulled away from how the block was
+    /// generated: a fork off its last branch, or an open gate.
+    pub fn is_switched(&self) -> bool {
+        match self {
+            Block::Rail(_) => false,
+            Block::Fork(fork) => fork.selected != fork.branches.len() - 1,
+            Block::Merge(merge) => merge.gate.is_some_and(|gate| gate.open),
+        }
+    }
+
+    /// Whether the block currently stops the train: a fork set to a wall, or
+    /// a closed gate.
+    pub fn is_set_to_wall(&self) -> bool {
+        match self {
+            Block::Rail(_) => false,
+            Block::Fork(fork) => fork.selected_rail().is_wall,
+            Block::Merge(merge) => merge.gate.is_some_and(|gate| !gate.open),
+        }
+    }
+
+    /// Puts the block where `pulls` pulls of its lever would have put it
+    /// since it was generated.
+    pub fn set_pulls(&mut self, pulls: usize) {
+        match self {
+            Block::Rail(_) => {}
+            Block::Fork(fork) => {
+                fork.selected = (fork.branches.len() - 1 + pulls) % fork.branches.len();
+            }
+            Block::Merge(merge) => {
+                if let Some(gate) = &mut merge.gate {
+                    gate.open = pulls % 2 == 1;
+                }
+            }
+        }
+    }
+
+    /// Pulls the lever of this block: cycles a fork, or opens or closes a
+    /// merge gate.
+    pub fn switch(&mut self) {
+        match self {
+            Block::Rail(_) => {}
+            Block::Fork(fork) => fork.cycle(),
+            Block::Merge(merge) => {
+                if let Some(gate) = &mut merge.gate {
+                    gate.open = !gate.open;
+                }
+            }
+        }
+    }
+}
+
+/// The track, stored as a graph: every rail links to the block it leads
+/// into, so the live branches of a fork can continue in different places.
+pub struct World {
+    pub track: Track,
+    /// Open rail end that newly generated blocks are attached to.
+    pub cursor: Option<RailRef>,
+    /// How many letters new forks and gates are labelled with, one per lever.
+    pub letter_count: usize,
+    pub seed: u64,
+    pub rng: StdRng,
+    pub presets: Rc<PresetLibrary>,
+}
+
+impl Fork {
+    pub fn selected_rail(&self) -> &Rail {
+        &self.branches[self.selected]
+    }
+
+    /// Selects the next branch, wrapping around after the last one.
+    pub fn cycle(&mut self) {
+        self.selected = (self.selected + 1) % self.branches.len();
+    }
+}
+
+impl World {
+    /// Creates an empty world that generates its track from `presets`,
+    /// driven by `seed`.
+    pub fn new(seed: u64, presets: Rc<PresetLibrary>) -> Self {
+        Self {
+            track: Track::new(),
+            cursor: None,
+            letter_count: DEFAULT_LEVERS,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            presets,
+        }
+    }
+
+    pub fn rail(&self, rail: RailRef) -> &Rail {
+        self.track[rail.block].rails()[rail.branch]
+    }
+
+    pub fn rail_mut(&mut self, rail: RailRef) -> &mut Rail {
+        match &mut self.track[rail.block] {
+            Block::Rail(block_rail) => block_rail,
+            Block::Fork(fork) => &mut fork.branches[rail.branch],
+            Block::Merge(merge) => {
+                if rail.branch == 1 {
+                    &mut merge.spur
+                } else {
+                    &mut merge.rail
+                }
+            }
+        }
+    }
+
+    /// Appends `block` after the cursor and moves the cursor to the block's
+    /// first exit without a wall, and returns the block's ID.
+    pub fn push_block(&mut self, block: Block) -> BlockId {
+        let cursor = self.cursor;
+        let id = self.track.push(block);
+
+        if let Some(cursor) = cursor {
+            self.link(
+                cursor,
+                RailRef {
+                    block: id,
+                    branch: 0,
+                },
+            );
+        }
+
+        self.cursor = self.track[id]
+            .exit_rails()
+            .iter()
+            .position(|rail| !rail.is_wall)
+            .map(|branch| RailRef { block: id, branch });
+        id
+    }
+
+    /// Makes `from` lead into `to`. The rails of a merge are entered
+    /// separately, while every branch of a fork is entered at once.
+    pub fn link(&mut self, from: RailRef, to: RailRef) {
+        self.rail_mut(from).next = Some(to);
+
+        if matches!(self.track[to.block], Block::Merge(_)) {
+            self.rail_mut(to).prev = Some(from);
+        } else {
+            for rail in self.track[to.block].rails_mut() {
+                rail.prev = Some(from);
+            }
+        }
+    }
+
+    /// Drops every block from `first_id` onwards, and detaches them from
+    /// `cursor`, which becomes the cursor again. Used to undo placing a piece.
+    pub fn rollback(&mut self, first_id: BlockId, cursor: Option<RailRef>) {
+        self.track.truncate(first_id);
+        self.cursor = cursor;
+
+        if let Some(cursor) = cursor {
+            self.rail_mut(cursor).next = None;
+        }
+    }
+
+    /// The rail leading into `block`, if it is still part of the track.
+    pub fn parent(&self, block: BlockId) -> Option<RailRef> {
+        self.track.get(block)?.rails()[0].prev
+    }
+
+    /// The rail the train runs on before `rail`, if it is still part of the
+    /// track. Every branch of a fork is entered from the same rail.
+    pub fn rail_before(&self, rail: RailRef) -> Option<RailRef> {
+        self.rail(rail).prev
+    }
+
+    /// The rail the train runs on after `rail`, which is `None` at a wall or
+    /// where the track is not generated yet. Merge spurs continue along the
+    /// main line.
+    pub fn rail_after(&self, rail: RailRef) -> Option<RailRef> {
+        match &self.track[rail.block] {
+            Block::Merge(merge) if rail.branch == 1 => merge.rail.next,
+            _ => self.rail(rail).next,
+        }
+    }
+
+    /// The track points just before and after `rail`, in world space, which
+    /// shape the ends of its curve.
+    pub fn rail_neighbours(&self, rail: RailRef) -> (Option<Vec2>, Option<Vec2>) {
+        let before = self.rail_before(rail).map(|before| {
+            let before = self.rail(before);
+            before.position + before.points[before.points.len() - 2]
+        });
+        let after = self.rail_after(rail).map(|after| {
+            let after = self.track[after.block].entered_rail(after.branch);
+            after.position + after.points[1]
+        });
+
+        (before, after)
+    }
+
+    /// World positions of a smooth curve along `rail`, carrying on from the
+    /// rails before and after it without a kink.
+    ///
+    /// Rails share their boundary points: the last point of a rail is also
+    /// the first point of the next one, so consecutive curves meet there.
+    /// Counting points along the track adds one less per rail, and anything
+    /// following the points visits each boundary twice, with rails built
+    /// separately leaving the copies a rounding error apart.
+    pub fn rail_curve(&self, rail: RailRef) -> Vec<Vec2> {
+        let points: Vec<Vec2> = self.rail(rail).world_points().collect();
+        let (before, after) = self.rail_neighbours(rail);
+
+        smooth_points(&points, before, after)
+    }
+
+    /// How many blocks away from `start` every block reachable from it is,
+    /// following every branch of every fork.
+    pub fn distances_from(&self, start: BlockId) -> BlockMap<usize> {
+        let mut distances = self.track.block_map();
+        distances.insert(start, 0);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(id) = queue.pop_front() {
+            let distance = distances[&id];
+
+            for rail in self.track[id].exit_rails() {
+                if let Some(next) = rail.next
+                    && !distances.contains_key(&next.block)
+                {
+                    distances.insert(next.block, distance + 1);
+                    queue.push_back(next.block);
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// Rails without a wall that nothing has been generated after yet, among
+    /// the blocks reachable from `start`.
+    pub fn open_ends_from(&self, start: BlockId) -> Vec<RailRef> {
+        let distances = self.distances_from(start);
+
+        self.track
+            .iter()
+            .filter(|(id, _)| distances.contains_key(id))
+            .flat_map(|(id, block)| {
+                block
+                    .exit_rails()
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, rail)| !rail.is_wall && rail.next.is_none())
+                    .map(move |(branch, _)| RailRef { block: id, branch })
+            })
+            .collect()
+    }
+
+    /// Removes every block `keep` returns false for. Links into removed
+    /// blocks are cleared; every other block keeps its ID.
+    pub fn retain_blocks(&mut self, mut keep: impl FnMut(BlockId) -> bool) {
+        let removed: Vec<BlockId> = self
+            .track
+            .iter()
+            .map(|(id, _)| id)
+            .filter(|&id| !keep(id))
+            .collect();
+
+        if removed.is_empty() {
+            return;
+        }
+
+        for &id in &removed {
+            self.track.remove(id);
+        }
+
+        let is_live = |rail: &RailRef| !removed.contains(&rail.block);
+
+        for block in self.track.blocks_mut() {
+            for rail in block.rails_mut() {
+                rail.next = rail.next.filter(is_live);
+                rail.prev = rail.prev.filter(is_live);
+            }
+        }
+
+        self.cursor = self.cursor.filter(is_live);
+    }
+
+    /// Finds the first fork, or gated merge, showing `letter` from block
+    /// `start` onwards, along the route the train would take with the
+    /// current selections.
+    pub fn find_next_fork_index(&self, start: BlockId, letter: Letter) -> Option<BlockId> {
+        let mut id = start;
+
+        loop {
+            let block = self.track.get(id)?;
+
+            if block.letter() == Some(letter) {
+                return Some(id);
+            }
+
+            id = block.next()?.block;
+        }
+    }
+
+    /// Moves every rail by `offset`, keeping the track's shape.
+    pub fn shift_origin(&mut self, offset: Vec2) {
+        for block in &mut self.track {
+            for rail in block.rails_mut() {
+                rail.position += offset;
+            }
+        }
+    }
+
+    /// The smooth curves of every rail of block `id`.
+    pub fn block_curves(&self, id: BlockId) -> BlockCurves {
+        BlockCurves::new(
+            (0..self.track[id].rails().len())
+                .map(|branch| self.rail_curve(RailRef { block: id, branch }))
+                .collect(),
+        )
+    }
+}
+
+/// The smooth curves of the rails of a block, indexed by branch, with the
+/// boxes around them so that blocks far apart are skipped when looking for
+/// crossings.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockCurves {
+    pub curves: Vec<Vec<Vec2>>,
+    bounds: Vec<(Vec2, Vec2)>,
+    block_bounds: (Vec2, Vec2),
+}
+
+impl BlockCurves {
+    pub fn new(curves: Vec<Vec<Vec2>>) -> Self {
+        let bounds: Vec<(Vec2, Vec2)> = curves.iter().map(|curve| bounds(curve)).collect();
+        let block_bounds = bounds.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), (curve_min, curve_max)| (min.min(*curve_min), max.max(*curve_max)),
+        );
+
+        Self {
+            curves,
+            bounds,
+            block_bounds,
+        }
+    }
+
+    /// Moves every curve by `offset`.
+    pub fn shift(&mut self, offset: Vec2) {
+        for point in self.curves.iter_mut().flatten() {
+            *point += offset;
+        }
+
+        for (min, max) in self.bounds.iter_mut().chain([&mut self.block_bounds]) {
+            *min += offset;
+            *max += offset;
+        }
+    }
+}
+
+/// Every point where a curve of block `block_a` crosses one of block
+/// `block_b`, which may be the same block. Curves that only meet at their
+/// ends, like consecutive rails or the branches of a fork, do not cross.
+pub fn curve_crossings(
+    block_a: BlockId,
+    curves_a: &BlockCurves,
+    block_b: BlockId,
+    curves_b: &BlockCurves,
+) -> Vec<Crossing> {
+    let mut crossings = Vec::new();
+
+    if !overlaps(curves_a.block_bounds, curves_b.block_bounds) {
+        return crossings;
+    }
+
+    for (i, points_a) in curves_a.curves.iter().enumerate() {
+        // Within a block, each pair of curves is only checked once.
+        let first_j = if block_a == block_b { i } else { 0 };
+
+        for (j, points_b) in curves_b.curves.iter().enumerate().skip(first_j) {
+            if !overlaps(curves_a.bounds[i], curves_b.bounds[j]) {
+                continue;
+            }
+
+            let same_curve = block_a == block_b && i == j;
+            let pieces_b = curve_pieces(points_b);
+
+            for (start_a, piece_a, piece_bounds_a) in curve_pieces(points_a) {
+                for &(start_b, piece_b, piece_bounds_b) in &pieces_b {
+                    if !overlaps(piece_bounds_a, piece_bounds_b) {
+                        continue;
+                    }
+
+                    for (k, segment_a) in piece_a.windows(2).enumerate() {
+                        for (l, segment_b) in piece_b.windows(2).enumerate() {
+                            // Within a curve, only segments further apart
+                            // can cross, and each pair is checked once.
+                            if same_curve && start_b + l < start_a + k + 2 {
+                                continue;
+                            }
+
+                            if let Some(position) = segment_intersection(
+                                segment_a[0],
+                                segment_a[1],
+                                segment_b[0],
+                                segment_b[1],
+                            ) {
+                                crossings.push(Crossing {
+                                    position,
+                                    blocks: [block_a, block_b],
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    crossings
+}
+
+/// `curve` cut into the pieces between consecutive track points, each with
+/// the index of its first segment and its bounds, so that pieces far apart
+/// are skipped as a whole.
+fn curve_pieces(curve: &[Vec2]) -> Vec<(usize, &[Vec2], (Vec2, Vec2))> {
+    (0..curve.len().saturating_sub(1))
+        .step_by(CURVE_SAMPLES)
+        .map(|start| {
+            let piece = &curve[start..=(start + CURVE_SAMPLES).min(curve.len() - 1)];
+            (start, piece, bounds(piece))
+        })
+        .collect()
+}
+
+/// The corners of the smallest box holding every one of `points`.
+fn bounds(points: &[Vec2]) -> (Vec2, Vec2) {
+    points.iter().fold(
+        (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+        |(min, max), point| (min.min(*point), max.max(*point)),
+    )
+}
+
+fn overlaps((min_a, max_a): (Vec2, Vec2), (min_b, max_b): (Vec2, Vec2)) -> bool {
+    !(min_a.cmpgt(max_b).any() || min_b.cmpgt(max_a).any())
+}
+
+/// A point where two rails of the track cross each other.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Crossing {
+    pub position: Vec2,
+    /// The blocks the two rails belong to, which may be the same one.
+    pub blocks: [BlockId; 2],
+}
+
+/// Where segments `a0`-`a1` and `b0`-`b1` cross, if they do away from their
+/// ends.
+pub fn segment_intersection(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> Option<Vec2> {
+    const END_MARGIN: f32 = 1e-3;
+
+    let a = a1 - a0;
+    let b = b1 - b0;
+    let denominator = a.perp_dot(b);
+
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let t = (b0 - a0).perp_dot(b) / denominator;
+    let u = (b0 - a0).perp_dot(a) / denominator;
+    let inside = END_MARGIN..1.0 - END_MARGIN;
+
+    (inside.contains(&t) && inside.contains(&u)).then(|| a0 + a * t)
+}
+
+/// Why a run ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeathCause {
+    /// The train ran off the end of a rail into a wall.
+    Wall,
+    /// The train ran into something else on the track.
+    Hazard(Hazard),
+}
+
+/// Things on the track other than walls that stop the train.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hazard {
+    /// A closed gate at the end of a merge spur.
+    ClosedGate,
+    /// A level crossing the rest of the train was still on.
+    OwnTrain,
+}
+
+impl DeathCause {
+    /// Short name, as stored in replays.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "wall",
+            DeathCause::Hazard(Hazard::ClosedGate) => "gate",
+            DeathCause::Hazard(Hazard::OwnTrain) => "crossing",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wall" => Some(DeathCause::Wall),
+            "gate" => Some(DeathCause::Hazard(Hazard::ClosedGate)),
+            "crossing" => Some(DeathCause::Hazard(Hazard::OwnTrain)),
+            _ => None,
+        }
+    }
+
+    /// What happened, as shown on the game over screen.
+    pub fn description(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "RAN INTO A WALL",
+            DeathCause::Hazard(Hazard::ClosedGate) => "RAN INTO A CLOSED GATE",
+            DeathCause::Hazard(Hazard::OwnTrain) => "HIT ITS OWN CARS AT A CROSSING",
+        }
+    }
+}
+
+/// How many of the track points the locomotive reached are remembered, which
+/// bounds how long a train can be.
+pub const TRAIL_POINTS: usize = 256;
+
+#[derive(Clone)]
+pub struct State {
+    /// Rails the train has entered blocks through, oldest first. The last one
+    /// is in the block the train is on.
+    pub path: Vec<RailRef>,
+    /// World positions of the track points the locomotive reached, oldest
+    /// first. Cars follow these rather than the track, which may have been
+    /// switched or trimmed under them.
+    pub trail: VecDeque<Vec2>,
+    pub current_point_idx: usize,
+    /// How far the train has run along the curve from the current point
+    /// towards the next one, in world units.
+    pub segment_distance: f32,
+    pub speedup_timer: f32,
+    pub alive: bool,
+    /// Why the run ended, once it has.
+    pub death_cause: Option<DeathCause>,
+    pub survival_ms: f32,
+    pub points_traversed: u32,
+    pub rails_traversed: u32,
+    pub forks_passed: u32,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            path: vec![RailRef {
+                block: 0,
+                branch: 0,
+            }],
+            trail: VecDeque::new(),
+            current_point_idx: 0,
+            segment_distance: 0.0,
+            speedup_timer: 0.0,
+            alive: true,
+            death_cause: None,
+            survival_ms: 0.0,
+            points_traversed: 0,
+            rails_traversed: 0,
+            forks_passed: 0,
+        }
+    }
+
+    /// Current train speed in world units per second.
+    pub fn current_speed(&self, ms_to_next_point: f32) -> f32 {
+        if self.alive {
+            game::POINT_SPACING * 1000.0 / ms_to_next_point
+        } else {
+            0.0
+        }
+    }
+
+    pub fn current_rail_idx(&self) -> BlockId {
+        self.path.last().unwrap().block
+    }
+
+    pub fn get_current_rail<'a>(&self, world: &'a World) -> &'a Block {
+        &world.track[self.current_rail_idx()]
+    }
+
+    /// The rail the train is on within the current block.
+    pub fn get_current_entered_rail<'a>(&self, world: &'a World) -> &'a Rail {
+        let entry = self.path.last().unwrap();
+
+        world.track[entry.block].entered_rail(entry.branch)
+    }
+
+    /// The rail the train enters after the current one, which is `None` at
+    /// a wall.
+    pub fn get_next_rail<'a>(&self, world: &'a World) -> Option<&'a Rail> {
+        self.get_current_rail(world)
+            .next()
+            .map(|next| world.track[next.block].entered_rail(next.branch))
+    }
+
+    /// Whether the train stops at the end of the rail it is on: at a wall, or
+    /// at a closed gate when it came in through a merge spur.
+    pub fn current_rail_is_blocked(&self, world: &World) -> bool {
+        self.current_rail_stop(world).is_some()
+    }
+
+    /// What stops the train at the end of the rail it is on, if anything.
+    pub fn current_rail_stop(&self, world: &World) -> Option<DeathCause> {
+        let entry = self.path.last().unwrap();
+        let block = &world.track[entry.block];
+
+        if block.entered_rail(entry.branch).is_wall {
+            return Some(DeathCause::Wall);
+        }
+
+        match block {
+            Block::Merge(merge)
+                if entry.branch == 1 && merge.gate.is_some_and(|gate| !gate.open) =>
+            {
+                Some(DeathCause::Hazard(Hazard::ClosedGate))
+            }
+            _ => None,
+        }
+    }
+
+    /// Ends the run.
+    pub fn crash(&mut self, cause: DeathCause) {
+        self.alive = false;
+        self.death_cause = Some(cause);
+    }
+
+    /// How many blocks away from the train every block is, counting both the
+    /// path behind it and every branch ahead. Blocks that are neither are
+    /// left out.
+    pub fn block_distances(&self, world: &World) -> BlockMap<usize> {
+        let mut distances = world.distances_from(self.current_rail_idx());
+
+        for (distance, entry) in self.path.iter().rev().enumerate() {
+            distances.insert(entry.block, distance);
+        }
+
+        distances
+    }
+
+    pub fn get_current_point_world_position(&self, world: &World) -> Vec2 {
+        let current_rail = self.get_current_entered_rail(world);
+
+        current_rail.position + current_rail.points[self.current_point_idx]
+    }
+
+    pub fn get_current_rail_points<'a>(&self, world: &'a World) -> &'a [Vec2] {
+        &self.get_current_entered_rail(world).points
+    }
+
+    pub fn get_next_point_world_position(&self, world: &World) -> Vec2 {
+        let current_rail = self.get_current_entered_rail(world);
+
+        let current_rail_points = &current_rail.points;
+
+        let current_rail_position = current_rail.position;
+
+        // The last point is shared with the next rail, see `World::rail_curve`.
+        let idx = (self.current_point_idx + 1).min(current_rail_points.len() - 1);
+
+        current_rail_points[idx] + current_rail_position
+    }
+
+    pub fn get_next_next_point_world_position(&self, world: &World) -> Vec2 {
+        let current_rail = self.get_current_entered_rail(world);
+
+        let current_rail_points = &current_rail.points;
+
+        let current_rail_position = current_rail.position;
+
+        if current_rail_points.len() > self.current_point_idx + 2 {
+            current_rail_points[self.current_point_idx + 2] + current_rail_position
+        } else if let Some(next_rail) = self.get_next_rail(world) {
+            next_rail.points[1] + next_rail.position
+        } else {
+            // Nothing follows a wall, so the train just keeps its heading.
+            *current_rail_points.last().unwrap() + current_rail_position
+        }
+    }
+
+    /// The last track point the train passed before the current one, if it
+    /// remembers one.
+    pub fn get_previous_point_world_position(&self) -> Option<Vec2> {
+        let current = *self.trail.back()?;
+
+        // The trail repeats shared boundary points, see `World::rail_curve`.
+        self.trail
+            .iter()
+            .rev()
+            .find(|point| point.distance(current) > 1e-2)
+            .copied()
+    }
+
+    /// The curve the train runs along from the current point to the next.
+    pub fn current_segment(&self, world: &World) -> CurveSegment {
+        CurveSegment::new(
+            self.get_previous_point_world_position(),
+            self.get_current_point_world_position(world),
+            self.get_next_point_world_position(world),
+            Some(self.get_next_next_point_world_position(world)),
+        )
+    }
+
+    /// Runs the train `distance` further along the curve, passing track
+    /// points on the way, unless it has crashed.
+    pub fn advance(&mut self, world: &World, distance: f32) {
+        if !self.alive {
+            return;
+        }
+
+        self.segment_distance += distance;
+
+        while self.alive {
+            let length = self.current_segment(world).length();
+
+            if self.segment_distance < length {
+                break;
+            }
+
+            self.segment_distance -= length;
+            self.move_to_next_point(world);
+        }
+    }
+
+    pub fn move_to_next_point(&mut self, world: &World) {
+        self.current_point_idx += 1;
+
+        let current_len = self.get_current_rail_points(world).len();
+        let current_rail = self.get_current_rail(world);
+        let stop = self.current_rail_stop(world);
+        if let Some(cause) = stop
+            && self.current_point_idx >= current_len - 1
+        {
+            self.crash(cause);
+            self.current_point_idx = current_len - 2;
+            return;
+        }
+
+        if self.current_point_idx < current_len {
+            self.points_traversed += 1;
+        }
+
+        if self.current_point_idx >= current_len {
+            if let Block::Fork(_) = current_rail {
+                self.forks_passed += 1;
+            }
+
+            self.rails_traversed += 1;
+            self.current_point_idx = 0;
+            self.path.push(
+                current_rail
+                    .next()
+                    .expect("the track is generated ahead of the train"),
+            );
+        }
+
+        self.record_trail_point(world);
+    }
+
+    /// Moves the trail along with the track when the world origin shifts.
+    pub fn shift_origin(&mut self, offset: Vec2) {
+        for point in &mut self.trail {
+            *point += offset;
+        }
+    }
+
+    /// Remembers the point the locomotive is on, for the cars to follow.
+    pub fn record_trail_point(&mut self, world: &World) {
+        if self.trail.len() == TRAIL_POINTS {
+            self.trail.pop_front();
+        }
+
+        self.trail
+            .push_back(self.get_current_point_world_position(world));
+    }
+}
+
+pub fn interpolate_angle(start: f32, end: f32, t: f32) -> f32 {
+    let two_pi = 2.0 * PI;
+    // Compute the difference, ensuring it is in the range [-PI, PI]
+    let diff = (end - start + PI).rem_euclid(two_pi) - PI;
+    // Interpolate by moving from start towards the target angle by t percent of the difference
+    let angle = start + diff * t;
+    // Normalize the result to the range [0, 2π)
+    angle.rem_euclid(two_pi)
+}
+
+impl Rail {
+    pub fn new_curved(
+        position: Vec2,
+        start_angle: f32,
+        is_wall: bool,
+        dist: f32,
+        n_points: usize,
+        angle_step: f32,
+    ) -> Self {
+        let mut circle_points = vec![];
+
+        circle_points.push(vec2(0.0, 0.0));
+
+        for i in 1..n_points {
+            let prev_point = circle_points[i - 1];
+            let x = dist * (start_angle + i as f32 * angle_step).cos();
+            let y = dist * (start_angle + i as f32 * angle_step).sin();
+            circle_points.push(vec2(prev_point.x + x, prev_point.y + y));
+        }
+
+        Self {
+            position,
+            points: circle_points,
+            is_wall,
+            next: None,
+            prev: None,
+        }
+    }
+
+    pub fn world_points(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.points.iter().map(|point| self.position + *point)
+    }
+
+    pub fn new_straight(
+        position: Vec2,
+        start_angle: f32,
+        is_wall: bool,
+        dist: f32,
+        n_points: usize,
+    ) -> Self {
+        Self::new_curved(position, start_angle, is_wall, dist, n_points, 0.0)
+    }
+
+    pub fn last_angle(&self) -> Angle {
+        let p1 = self.points.get(self.points.len() - 2).unwrap();
+        let p2 = self.points.last().unwrap();
+
+        // Calculate direction vector between points
+        let dx = p2.x - p1.x;
+        let dy = p2.y - p1.y;
+
+        // Calculate angle using arctangent (atan2 handles all quadrants correctly)
+        dy.atan2(dx)
+    }
+}
+
+impl Merge {
+    /// Builds the merge closing a symmetric bypass. `spur_out` left the main
+    /// line heading `start_angle`, and `position` is half way along the main
+    /// line, level with the end of `spur_out`. The main line runs straight on
+    /// from there, and the spur mirrors `spur_out` back into it, so both take
+    /// the same number of points to reach the join.
+    pub fn new_bypass(
+        position: Vec2,
+        start_angle: Angle,
+        spur_out: &Rail,
+        gate: Option<Gate>,
+    ) -> Self {
+        let direction = Vec2::from_angle(start_angle);
+        let mirror = |point: Vec2| point - 2.0 * (point - position).dot(direction) * direction;
+
+        let mut spur_points: Vec<Vec2> = spur_out.world_points().map(mirror).collect();
+        spur_points.reverse();
+
+        let spur_position = spur_points[0];
+        let join = *spur_points.last().unwrap();
+
+        let n_points = spur_points.len();
+        let rail = Rail::new_straight(
+            position,
+            start_angle,
+            false,
+            position.distance(join) / (n_points - 1) as f32,
+            n_points,
+        );
+
+        let spur = Rail {
+            position: spur_position,
+            points: spur_points
+                .iter()
+                .map(|point| *point - spur_position)
+                .collect(),
+            is_wall: false,
+            next: None,
+            prev: None,
+        };
+
+        Self { rail, spur, gate }
+    }
+}
+
+/// World position of the end of the rail at the cursor, where the next
+/// generated block starts.
+pub fn get_last_rail_world_position(world: &World) -> Vec2 {
+    match world.cursor {
+        Some(cursor) => {
+            let rail = world.rail(cursor);
+            rail.position + *rail.points.last().unwrap()
+        }
+        None => vec2(0.0, 0.0),
+    }
+}
+
+/// Heading at the end of the rail at the cursor.
+pub fn get_last_rail_world_start_angle(world: &World) -> Angle {
+    match world.cursor {
+        Some(cursor) => world.rail(cursor).last_angle(),
+        None => 0.0,
+    }
+}
@@ -1,134 +1,360 @@
-use std::f32::consts::PI;
-
 use macroquad::math::Vec2;
-use rand::seq::IndexedRandom;
+use rand::{Rng, distr::weighted::WeightedIndex, seq::index};
 
 use crate::{
-    Angle, Block, Fork, ForkSelection, Letter, Rail, World, get_last_rail_world_position,
+    Angle, Block, Fork, Gate, Letter, Merge, Rail, RailRef, World, get_last_rail_world_position,
     get_last_rail_world_start_angle,
+    preset_library::{BlockDef, LetterRule, Mirror, PresetDef, RailShape, STARTING_PRESET},
+    track::BlockId,
 };
 
 pub fn get_flipped_mult(flipped: bool) -> f32 {
-    if flipped { 1.0 } else { -1.0 }
-}
-
-pub fn make_rail_straight(position: Vec2, start_angle: Angle) -> Rail {
-    Rail::new_straight(position, start_angle, false, 30.0, 8)
+    if flipped { -1.0 } else { 1.0 }
 }
 
-pub fn make_rail_90_turn(position: Vec2, start_angle: Angle, is_wall: bool, flipped: bool) -> Rail {
+pub fn make_rail(
+    position: Vec2,
+    start_angle: Angle,
+    shape: RailShape,
+    is_wall: bool,
+    flipped: bool,
+) -> Rail {
     Rail::new_curved(
         position,
         start_angle,
         is_wall,
-        30.0,
-        7,
-        get_flipped_mult(flipped) * PI / 16.0,
+        shape.spacing,
+        shape.n_points,
+        get_flipped_mult(flipped) * shape.angle_step,
     )
 }
 
-pub fn make_rail_u_turn(position: Vec2, start_angle: Angle, is_wall: bool, flipped: bool) -> Rail {
-    Rail::new_curved(
-        position,
-        start_angle,
-        is_wall,
-        30.0,
-        5,
-        get_flipped_mult(flipped) * PI / 8.0,
-    )
+fn resolve_mirror(world: &mut World, mirror: Mirror) -> bool {
+    match mirror {
+        Mirror::None => false,
+        Mirror::Random => random_number(world, 2) == 1,
+        Mirror::Always => true,
+    }
 }
 
-pub fn preset_0_straight(world: &mut World) {
-    world.rails.push(Block::Rail(make_rail_straight(
-        get_last_rail_world_position(world),
-        get_last_rail_world_start_angle(world),
-    )));
+pub fn random_letter(world: &mut World) -> Letter {
+    pick_letter(world, &Letter::first(world.letter_count))
 }
 
-pub fn preset_1_u_turn(world: &mut World) {
-    world.rails.push(Block::Rail(make_rail_u_turn(
-        get_last_rail_world_position(world),
-        get_last_rail_world_start_angle(world),
-        false,
-        random_number(2) == 1,
-    )));
+/// Picks one of `letters` at random, without touching the RNG if there is
+/// only one.
+fn pick_letter(world: &mut World, letters: &[Letter]) -> Letter {
+    match letters.len() {
+        1 => letters[0],
+        n => letters[random_number(world, n as i32) as usize - 1],
+    }
 }
 
-pub fn preset_2_90_turn(world: &mut World) {
-    world.rails.push(Block::Rail(make_rail_90_turn(
-        get_last_rail_world_position(world),
-        get_last_rail_world_start_angle(world),
-        false,
-        random_number(2) == 1,
-    )));
-}
+/// Minimum distance kept between a new piece and the existing track, so forks
+/// and their letters do not overlap older rails.
+pub const MIN_TRACK_CLEARANCE: f32 = 75.0;
+
+/// How many of the newest blocks a new piece is checked against. Rails fade
+/// out ten blocks away from the train, so older blocks are never on screen
+/// together with the new piece.
+const RECENT_TRACK_BLOCKS: usize = 20;
+
+/// How many presets are tried before falling back to a straight.
+const MAX_PLACEMENT_ATTEMPTS: usize = 8;
+
+/// Most straights padded in front of a fork to keep its letter fair.
+const MAX_PADDING_STRAIGHTS: usize = 8;
+
+/// Number of track points between the start of the newest fork showing
+/// `letter` on the way to the cursor and the cursor itself, or `None` if
+/// there is no such fork.
+pub fn points_since_fork(world: &World, letter: Letter) -> Option<usize> {
+    let mut points = 0;
+    let mut rail = world.cursor?;
 
-pub fn random_letter() -> Letter {
-    match random_number(3) {
-        1 => Letter::A,
-        2 => Letter::B,
-        _ => Letter::C,
+    loop {
+        // Boundary points are shared, see `World::rail_curve`.
+        points += world.rail(rail).points.len() - 1;
+
+        if world.track[rail.block].letter() == Some(letter) {
+            return Some(points);
+        }
+
+        rail = world.parent(rail.block)?;
     }
 }
 
-pub fn preset_3_fork_90_symmetrical_turn(world: &mut World) {
-    let position = get_last_rail_world_position(world);
-    let start_angle = get_last_rail_world_start_angle(world);
+/// Whether a fork or gate showing `letter` at the cursor would be fair at
+/// `ms_to_next_point`. Once the train enters a fork, pulling that letter's
+/// lever switches the next fork or gate with the same letter, so the player
+/// needs at least `min_reaction_ms` between the two.
+pub fn is_fair_letter(world: &World, letter: Letter, ms_to_next_point: f32) -> bool {
+    points_since_fork(world, letter)
+        .is_none_or(|points| points as f32 * ms_to_next_point >= world.presets.min_reaction_ms)
+}
 
-    let random_bool = random_number(2) == 1;
-    let random_bool2 = random_number(2) == 1;
+/// Letters allowed by `rule` for a lever-controlled block at the cursor. A
+/// fixed letter without a lever in play allows any lever instead. With
+/// `ms_to_next_point`, only letters fair at that speed are kept.
+fn allowed_letters(world: &World, rule: LetterRule, ms_to_next_point: Option<f32>) -> Vec<Letter> {
+    let levers = Letter::first(world.letter_count);
+    let candidates = match rule.fixed_letter() {
+        Some(letter) if levers.contains(&letter) => vec![letter],
+        _ => levers,
+    };
 
-    world.rails.push(Block::Fork(Fork {
-        which: ForkSelection::Rail2,
-        rail1: make_rail_90_turn(position, start_angle, random_bool, random_bool2),
-        rail2: make_rail_90_turn(position, start_angle, !random_bool, !random_bool2),
-        letter: random_letter(),
-    }));
+    match ms_to_next_point {
+        Some(ms_to_next_point) => candidates
+            .into_iter()
+            .filter(|&letter| is_fair_letter(world, letter, ms_to_next_point))
+            .collect(),
+        None => candidates,
+    }
 }
 
-pub fn preset_4_fork_u_turn_symmetrical_turn(world: &mut World) {
-    let position = get_last_rail_world_position(world);
-    let start_angle = get_last_rail_world_start_angle(world);
+/// Picks a fork letter allowed by `rule`, padding the track with straights
+/// until one is fair. If none is after `MAX_PADDING_STRAIGHTS`, the letter
+/// whose last fork is furthest back is taken.
+fn fork_letter(world: &mut World, rule: LetterRule, ms_to_next_point: Option<f32>) -> Letter {
+    for _ in 0..MAX_PADDING_STRAIGHTS {
+        let letters = allowed_letters(world, rule, ms_to_next_point);
+
+        if !letters.is_empty() {
+            return pick_letter(world, &letters);
+        }
 
-    let random_bool = random_number(2) == 1;
-    let random_bool2 = random_number(2) == 1;
+        spawn_preset(world, STARTING_PRESET);
+    }
 
-    world.rails.push(Block::Fork(Fork {
-        which: ForkSelection::Rail2,
-        rail1: make_rail_u_turn(position, start_angle, random_bool, random_bool2),
-        rail2: make_rail_u_turn(position, start_angle, !random_bool, !random_bool2),
-        letter: random_letter(),
-    }));
+    allowed_letters(world, rule, None)
+        .into_iter()
+        .max_by_key(|&letter| points_since_fork(world, letter).unwrap_or(usize::MAX))
+        .unwrap()
 }
 
-pub fn preset_random(world: &mut World) {
-    if random_number(100) >= 50 {
-        preset_0_straight(world);
-    } else {
-        if random_number(100) >= 75 {
-            if random_number(100) >= 75 {
-                preset_2_90_turn(world);
-                preset_0_straight(world);
-            } else {
-                preset_1_u_turn(world);
-                preset_0_straight(world);
+/// Appends one block of a preset. With `ms_to_next_point`, fork and gate
+/// letters are kept fair at that speed; without it they are placed as rolled.
+fn spawn_block(world: &mut World, block: &BlockDef, mirrored: bool, ms_to_next_point: Option<f32>) {
+    match block {
+        BlockDef::Rail { shape, mirror } => {
+            let position = get_last_rail_world_position(world);
+            let start_angle = get_last_rail_world_start_angle(world);
+            let flipped = resolve_mirror(world, *mirror) != mirrored;
+
+            world.push_block(Block::Rail(make_rail(
+                position,
+                start_angle,
+                *shape,
+                false,
+                flipped,
+            )));
+        }
+        BlockDef::Fork {
+            branches,
+            mirror,
+            random_walls,
+            letter,
+        } => {
+            let letter = fork_letter(world, *letter, ms_to_next_point);
+
+            // Padding may have been added in front of the fork.
+            let position = get_last_rail_world_position(world);
+            let start_angle = get_last_rail_world_start_angle(world);
+            let flipped = resolve_mirror(world, *mirror) != mirrored;
+
+            let open_branches: Vec<usize> =
+                (0..branches.len()).filter(|&i| !branches[i].wall).collect();
+            let walled: Vec<usize> =
+                index::sample(&mut world.rng, open_branches.len(), *random_walls)
+                    .into_iter()
+                    .map(|i| open_branches[i])
+                    .collect();
+
+            let rails: Vec<Rail> = branches
+                .iter()
+                .enumerate()
+                .map(|(i, branch)| {
+                    make_rail(
+                        position,
+                        start_angle,
+                        branch.shape,
+                        branch.wall || walled.contains(&i),
+                        flipped != branch.mirrored,
+                    )
+                })
+                .collect();
+
+            world.push_block(Block::Fork(Fork {
+                selected: rails.len() - 1,
+                branches: rails,
+                letter,
+            }));
+        }
+        BlockDef::Merge {
+            spur,
+            mirror,
+            letter,
+            gate,
+        } => {
+            let letter = fork_letter(world, *letter, ms_to_next_point);
+
+            let position = get_last_rail_world_position(world);
+            let start_angle = get_last_rail_world_start_angle(world);
+            let flipped = resolve_mirror(world, *mirror) != mirrored;
+
+            // The main line reaches half way along the bypass while the spur
+            // leaves it, and the merge brings the spur back symmetrically.
+            let spur_out = make_rail(position, start_angle, *spur, false, flipped);
+            let direction = Vec2::from_angle(start_angle);
+            let spur_end = spur_out.world_points().last().unwrap();
+            let half_length = (spur_end - position).dot(direction);
+            let main_line = Rail::new_straight(
+                position,
+                start_angle,
+                false,
+                half_length / (spur.n_points - 1) as f32,
+                spur.n_points,
+            );
+            let mut merge = Merge::new_bypass(
+                position + direction * half_length,
+                start_angle,
+                &spur_out,
+                None,
+            );
+
+            let fork_id = world.push_block(Block::Fork(Fork {
+                branches: vec![main_line, spur_out],
+                selected: 1,
+                letter,
+            }));
+
+            // Gates cannot be padded away from the fork, so a merge without a
+            // fair letter gets no gate.
+            if let Some(rule) = gate {
+                let letters = allowed_letters(world, *rule, ms_to_next_point);
+
+                if !letters.is_empty() {
+                    merge.gate = Some(Gate {
+                        letter: pick_letter(world, &letters),
+                        open: false,
+                    });
+                }
             }
+
+            let merge_id = world.push_block(Block::Merge(merge));
+            world.link(
+                RailRef {
+                    block: fork_id,
+                    branch: 1,
+                },
+                RailRef {
+                    block: merge_id,
+                    branch: 1,
+                },
+            );
+        }
+    }
+}
+
+fn spawn_preset_def(
+    world: &mut World,
+    preset: &PresetDef,
+    mirrored: bool,
+    ms_to_next_point: Option<f32>,
+) {
+    for block in &preset.blocks {
+        spawn_block(world, block, mirrored, ms_to_next_point);
+    }
+}
+
+/// Appends every block of the preset called `name` at the cursor, with fork
+/// letters placed as rolled.
+pub fn spawn_preset(world: &mut World, name: &str) {
+    let presets = world.presets.clone();
+    let preset = presets
+        .get(name)
+        .unwrap_or_else(|| panic!("unknown preset '{}'", name));
+
+    spawn_preset_def(world, preset, false, None);
+}
+
+/// Whether the blocks from `first_new_block` onwards come closer than
+/// `MIN_TRACK_CLEARANCE` to the recent track, fork branches included. Points
+/// around `junction`, where the new blocks attach, are ignored on both sides.
+pub fn collides_with_track(world: &World, first_new_block: BlockId, junction: Vec2) -> bool {
+    let is_new = |(id, _): &(BlockId, &Block)| *id >= first_new_block;
+    let existing = world
+        .track
+        .iter()
+        .rev()
+        .filter(|entry| !is_new(entry))
+        .take(RECENT_TRACK_BLOCKS);
+    let added = world.track.iter().filter(is_new);
+
+    let away_from_junction = |point: &Vec2| point.distance(junction) >= MIN_TRACK_CLEARANCE;
+
+    let obstacles: Vec<Vec2> = existing
+        .flat_map(|(_, block)| block.rails())
+        .flat_map(|rail| rail.world_points())
+        .filter(away_from_junction)
+        .collect();
+
+    added
+        .flat_map(|(_, block)| block.rails())
+        .flat_map(|rail| rail.world_points())
+        .filter(away_from_junction)
+        .any(|point| {
+            obstacles
+                .iter()
+                .any(|obstacle| obstacle.distance(point) < MIN_TRACK_CLEARANCE)
+        })
+}
+
+/// Appends a preset picked at random at the cursor, weighted by how likely
+/// each preset is at `difficulty`. Presets that split the track are only
+/// picked if `can_split`. Presets that would run into the existing track are
+/// tried mirrored, then re-rolled, and a straight is placed if nothing fits.
+/// Forks get letters the player can react to at `ms_to_next_point`, with
+/// straights padded in front of them when no letter is far enough from its
+/// last fork.
+pub fn preset_random(world: &mut World, difficulty: f32, ms_to_next_point: f32, can_split: bool) {
+    let presets = world.presets.clone();
+    let weights = presets.presets.iter().map(|preset| {
+        if preset.splits() && !can_split {
+            0.0
         } else {
-            if random_number(100) >= 75 {
-                preset_3_fork_90_symmetrical_turn(world);
-                preset_0_straight(world);
-            } else {
-                preset_4_fork_u_turn_symmetrical_turn(world);
-                preset_0_straight(world);
+            preset.weight_at(difficulty)
+        }
+    });
+
+    // Only splitting presets may have any weight left.
+    let Ok(distribution) = WeightedIndex::new(weights) else {
+        spawn_preset(world, STARTING_PRESET);
+        return;
+    };
+
+    let junction = get_last_rail_world_position(world);
+    let first_new_block = world.track.next_id();
+    let cursor = world.cursor;
+
+    for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+        let preset = &presets.presets[world.rng.sample(&distribution)];
+
+        for mirrored in [false, true] {
+            spawn_preset_def(world, preset, mirrored, Some(ms_to_next_point));
+
+            if !collides_with_track(world, first_new_block, junction) {
+                return;
             }
+
+            world.rollback(first_new_block, cursor);
         }
     }
-}
 
-fn random_number(max: i32) -> i32 {
-    // using rand because macroquad's rand always return the same numbers
-    let mut rng = rand::rng();
-    let nums: Vec<i32> = (1..=max).collect();
+    spawn_preset(world, STARTING_PRESET);
+}
 
-    *nums.choose(&mut rng).unwrap()
+/// Returns a number in `1..=max` from the world's seeded RNG.
+fn random_number(world: &mut World, max: i32) -> i32 {
+    world.rng.random_range(1..=max)
 }
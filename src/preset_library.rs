@@ -0,0 +1,528 @@
+use std::{collections::BTreeMap, f32::consts::FRAC_PI_2, fmt, fs, io, ops::Range, path::Path};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::Letter;
+
+/// The preset file shipped with the game, used when no other file is loaded.
+const BUILTIN_PRESETS: &str = include_str!("../presets.toml");
+
+/// Every track starts with a few of these, so it must always exist. It is
+/// also used to pad the track in front of forks, so it cannot have any.
+pub const STARTING_PRESET: &str = "straight";
+
+/// Reaction time kept between forks with the same letter when the preset
+/// file does not set `min_reaction_ms`.
+pub const DEFAULT_MIN_REACTION_MS: f32 = 600.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RailShape {
+    pub spacing: f32,
+    pub n_points: usize,
+    /// Heading change between consecutive points, in radians.
+    pub angle_step: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mirror {
+    #[default]
+    None,
+    Random,
+    Always,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LetterRule {
+    #[default]
+    Random,
+    Fixed(Letter),
+}
+
+impl LetterRule {
+    pub fn fixed_letter(&self) -> Option<Letter> {
+        match self {
+            LetterRule::Random => None,
+            LetterRule::Fixed(letter) => Some(*letter),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(LetterRule::Random),
+            _ => Letter::from_name(name).map(LetterRule::Fixed),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BranchDef {
+    pub shape: RailShape,
+    pub mirrored: bool,
+    pub wall: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum BlockDef {
+    Rail {
+        shape: RailShape,
+        mirror: Mirror,
+    },
+    Fork {
+        branches: Vec<BranchDef>,
+        mirror: Mirror,
+        random_walls: usize,
+        letter: LetterRule,
+    },
+    /// A fork whose spur leaves along `spur` and rejoins the main line at a
+    /// merge, which has a gate if `gate` is set.
+    Merge {
+        spur: RailShape,
+        mirror: Mirror,
+        letter: LetterRule,
+        gate: Option<LetterRule>,
+    },
+}
+
+impl BlockDef {
+    /// How many branches of the block the track continues from.
+    pub fn open_branches(&self) -> usize {
+        match self {
+            BlockDef::Rail { .. } | BlockDef::Merge { .. } => 1,
+            BlockDef::Fork {
+                branches,
+                random_walls,
+                ..
+            } => branches.iter().filter(|branch| !branch.wall).count() - random_walls,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PresetDef {
+    pub name: String,
+    pub blocks: Vec<BlockDef>,
+    /// Relative chance of being picked at the start of a run.
+    pub weight: f32,
+    /// Relative chance of being picked at top speed.
+    pub hard_weight: f32,
+}
+
+impl PresetDef {
+    /// Relative chance of being picked at `difficulty`, which goes from 0 at
+    /// the start of a run to 1 at top speed.
+    pub fn weight_at(&self, difficulty: f32) -> f32 {
+        self.weight + (self.hard_weight - self.weight) * difficulty.clamp(0.0, 1.0)
+    }
+
+    /// Whether the preset ends in a fork that leaves several branches open,
+    /// each continuing as its own track.
+    pub fn splits(&self) -> bool {
+        self.blocks
+            .last()
+            .is_some_and(|block| block.open_branches() > 1)
+    }
+}
+
+/// Every track piece the generator can place, as loaded from a preset file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PresetLibrary {
+    pub presets: Vec<PresetDef>,
+    /// Least time the player gets between entering a fork and reaching the
+    /// next fork with the same letter.
+    pub min_reaction_ms: f32,
+    /// Hash of the file the presets were parsed from. Replays record it, as
+    /// the same seed generates a different track from different presets.
+    pub hash: u64,
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(io::Error),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(error) => write!(f, "{}", error),
+            PresetError::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+impl From<io::Error> for PresetError {
+    fn from(error: io::Error) -> Self {
+        PresetError::Io(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFile {
+    min_reaction_ms: Option<Spanned<f32>>,
+    #[serde(default)]
+    rails: BTreeMap<String, Spanned<RawRail>>,
+    #[serde(default)]
+    preset: Vec<Spanned<RawPreset>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRail {
+    spacing: f32,
+    points: usize,
+    angle_step_degrees: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPreset {
+    name: Spanned<String>,
+    weight: f32,
+    hard_weight: Option<f32>,
+    blocks: Vec<Spanned<RawBlock>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBlock {
+    rail: Option<Spanned<String>>,
+    fork: Option<Vec<Spanned<RawBranch>>>,
+    merge: Option<Spanned<String>>,
+    #[serde(default)]
+    mirror: Mirror,
+    walls: Option<usize>,
+    letter: Option<Spanned<String>>,
+    gate: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBranch {
+    rail: Spanned<String>,
+    #[serde(default)]
+    mirrored: bool,
+    #[serde(default)]
+    wall: bool,
+}
+
+/// Resolves byte offsets in the source text to line numbers.
+struct Source<'a> {
+    text: &'a str,
+}
+
+impl Source<'_> {
+    fn line(&self, span: Range<usize>) -> usize {
+        let offset = span.start.min(self.text.len());
+        self.text[..offset].matches('\n').count() + 1
+    }
+
+    fn error(&self, span: Range<usize>, message: String) -> PresetError {
+        PresetError::Invalid {
+            line: self.line(span),
+            message,
+        }
+    }
+}
+
+impl PresetLibrary {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_PRESETS).expect("built-in presets.toml is invalid")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PresetError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, PresetError> {
+        let source = Source { text };
+
+        let raw: RawFile = toml::from_str(text).map_err(|error| PresetError::Invalid {
+            line: error.span().map_or(1, |span| source.line(span)),
+            message: error.message().to_owned(),
+        })?;
+
+        let mut rails = BTreeMap::new();
+
+        for (name, raw_rail) in &raw.rails {
+            let span = raw_rail.span();
+            let raw_rail = raw_rail.get_ref();
+
+            if raw_rail.points < 2 {
+                return Err(source.error(span, format!("rail '{}' needs at least 2 points", name)));
+            }
+
+            if raw_rail.spacing <= 0.0 {
+                return Err(source.error(span, format!("rail '{}' needs a positive spacing", name)));
+            }
+
+            rails.insert(
+                name.as_str(),
+                RailShape {
+                    spacing: raw_rail.spacing,
+                    n_points: raw_rail.points,
+                    angle_step: raw_rail.angle_step_degrees.to_radians(),
+                },
+            );
+        }
+
+        let find_rail = |name: &Spanned<String>| {
+            rails.get(name.get_ref().as_str()).copied().ok_or_else(|| {
+                source.error(name.span(), format!("unknown rail '{}'", name.get_ref()))
+            })
+        };
+
+        let find_letter = |name: &Spanned<String>| {
+            LetterRule::from_name(name.get_ref()).ok_or_else(|| {
+                source.error(
+                    name.span(),
+                    format!(
+                        "unknown letter '{}', expected \"random\" or A to F",
+                        name.get_ref()
+                    ),
+                )
+            })
+        };
+
+        let mut presets: Vec<PresetDef> = Vec::new();
+
+        for raw_preset in &raw.preset {
+            let span = raw_preset.span();
+            let raw_preset = raw_preset.get_ref();
+            let name = raw_preset.name.get_ref();
+
+            if presets.iter().any(|preset| &preset.name == name) {
+                return Err(source.error(
+                    raw_preset.name.span(),
+                    format!("duplicate preset '{}'", name),
+                ));
+            }
+
+            let weight = raw_preset.weight;
+            let hard_weight = raw_preset.hard_weight.unwrap_or(weight);
+
+            if !(weight >= 0.0 && hard_weight >= 0.0) {
+                return Err(source.error(span, format!("preset '{}' has a negative weight", name)));
+            }
+
+            if raw_preset.blocks.is_empty() {
+                return Err(source.error(span, format!("preset '{}' has no blocks", name)));
+            }
+
+            let mut blocks = Vec::new();
+
+            for (block_idx, raw_block) in raw_preset.blocks.iter().enumerate() {
+                let is_last = block_idx + 1 == raw_preset.blocks.len();
+                let span = raw_block.span();
+                let raw_block = raw_block.get_ref();
+
+                if raw_block.gate.is_some() && raw_block.merge.is_none() {
+                    return Err(source.error(span, "'gate' only applies to merges".to_owned()));
+                }
+
+                let block = match (&raw_block.rail, &raw_block.fork, &raw_block.merge) {
+                    (Some(rail), None, None) => {
+                        if raw_block.walls.is_some() || raw_block.letter.is_some() {
+                            return Err(source.error(
+                                span,
+                                "'walls' and 'letter' only apply to forks".to_owned(),
+                            ));
+                        }
+
+                        BlockDef::Rail {
+                            shape: find_rail(rail)?,
+                            mirror: raw_block.mirror,
+                        }
+                    }
+                    (None, Some(raw_branches), None) => {
+                        if raw_branches.len() < 2 {
+                            return Err(source.error(
+                                span,
+                                format!(
+                                    "a fork needs at least 2 branches, found {}",
+                                    raw_branches.len()
+                                ),
+                            ));
+                        }
+
+                        let mut branches = Vec::new();
+
+                        for raw_branch in raw_branches {
+                            let raw_branch = raw_branch.get_ref();
+
+                            branches.push(BranchDef {
+                                shape: find_rail(&raw_branch.rail)?,
+                                mirrored: raw_branch.mirrored,
+                                wall: raw_branch.wall,
+                            });
+                        }
+
+                        let random_walls = raw_block.walls.unwrap_or(0);
+                        let open_branches = branches.iter().filter(|branch| !branch.wall).count();
+
+                        if open_branches <= random_walls {
+                            return Err(source.error(
+                                span,
+                                "a fork must leave at least one branch without a wall".to_owned(),
+                            ));
+                        }
+
+                        if open_branches - random_walls > 1 && !is_last {
+                            return Err(source.error(
+                                span,
+                                "a fork leaving several branches open must be the last block"
+                                    .to_owned(),
+                            ));
+                        }
+
+                        BlockDef::Fork {
+                            branches,
+                            mirror: raw_block.mirror,
+                            random_walls,
+                            letter: raw_block
+                                .letter
+                                .as_ref()
+                                .map_or(Ok(LetterRule::Random), find_letter)?,
+                        }
+                    }
+                    (None, None, Some(spur)) => {
+                        if raw_block.walls.is_some() {
+                            return Err(
+                                source.error(span, "'walls' only applies to forks".to_owned())
+                            );
+                        }
+
+                        // The bypass is as long as the spur reaches ahead, so
+                        // the spur has to head forwards all the way to its end.
+                        let shape = find_rail(spur)?;
+                        let turn = shape.angle_step.abs() * (shape.n_points - 1) as f32;
+
+                        if turn >= FRAC_PI_2 {
+                            return Err(source.error(
+                                spur.span(),
+                                format!(
+                                    "merge spur '{}' turns back, it must turn less than 90 degrees",
+                                    spur.get_ref()
+                                ),
+                            ));
+                        }
+
+                        BlockDef::Merge {
+                            spur: shape,
+                            mirror: raw_block.mirror,
+                            letter: raw_block
+                                .letter
+                                .as_ref()
+                                .map_or(Ok(LetterRule::Random), find_letter)?,
+                            gate: raw_block.gate.as_ref().map(find_letter).transpose()?,
+                        }
+                    }
+                    _ => {
+                        return Err(source.error(
+                            span,
+                            "a block needs exactly one of 'rail', 'fork' or 'merge'".to_owned(),
+                        ));
+                    }
+                };
+
+                blocks.push(block);
+            }
+
+            presets.push(PresetDef {
+                name: name.clone(),
+                blocks,
+                weight,
+                hard_weight,
+            });
+        }
+
+        match presets.iter().find(|preset| preset.name == STARTING_PRESET) {
+            None => {
+                return Err(PresetError::Invalid {
+                    line: 1,
+                    message: format!("missing required preset '{}'", STARTING_PRESET),
+                });
+            }
+            Some(preset)
+                if preset
+                    .blocks
+                    .iter()
+                    .any(|block| !matches!(block, BlockDef::Rail { .. })) =>
+            {
+                let span = raw
+                    .preset
+                    .iter()
+                    .find(|raw_preset| raw_preset.get_ref().name.get_ref() == STARTING_PRESET)
+                    .unwrap()
+                    .span();
+
+                return Err(source.error(
+                    span,
+                    format!("preset '{}' can only contain rails", STARTING_PRESET),
+                ));
+            }
+            Some(_) => {}
+        }
+
+        let min_reaction_ms = match &raw.min_reaction_ms {
+            Some(value) if !(0.0..).contains(value.get_ref()) => {
+                return Err(source.error(
+                    value.span(),
+                    "'min_reaction_ms' cannot be negative".to_owned(),
+                ));
+            }
+            Some(value) => *value.get_ref(),
+            None => DEFAULT_MIN_REACTION_MS,
+        };
+
+        // Weights are interpolated linearly, so checking both ends covers
+        // every difficulty in between.
+        for difficulty in [0.0, 1.0] {
+            if presets
+                .iter()
+                .all(|preset| preset.weight_at(difficulty) <= 0.0)
+            {
+                return Err(PresetError::Invalid {
+                    line: 1,
+                    message: format!("every preset has zero weight at difficulty {}", difficulty),
+                });
+            }
+        }
+
+        Ok(Self {
+            presets,
+            min_reaction_ms,
+            hash: fnv1a(text.as_bytes()),
+        })
+    }
+
+    /// Probability of each preset being picked at `difficulty`, in file order.
+    pub fn distribution(&self, difficulty: f32) -> Vec<(&str, f32)> {
+        let total: f32 = self
+            .presets
+            .iter()
+            .map(|preset| preset.weight_at(difficulty))
+            .sum();
+
+        self.presets
+            .iter()
+            .map(|preset| (preset.name.as_str(), preset.weight_at(difficulty) / total))
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PresetDef> {
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher is guaranteed to
+/// give the same result on every platform and Rust version.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
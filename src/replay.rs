@@ -0,0 +1,301 @@
+use std::{fmt, fs, io, path::Path};
+
+use crate::{
+    DeathCause,
+    game::{Inputs, LeverSettings, LeverTargeting, TrainSettings},
+};
+
+/// Bumped whenever the file layout or the simulation changes in a way that
+/// would make older replays play back differently.
+pub const REPLAY_VERSION: u32 = 16;
+
+const REPLAY_HEADER: &str = "railway-panic-replay";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputKind {
+    SelectLeft,
+    SelectRight,
+    PullLever,
+}
+
+impl InputKind {
+    fn name(&self) -> &'static str {
+        match self {
+            InputKind::SelectLeft => "left",
+            InputKind::SelectRight => "right",
+            InputKind::PullLever => "lever",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(InputKind::SelectLeft),
+            "right" => Some(InputKind::SelectRight),
+            "lever" => Some(InputKind::PullLever),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReplayInput {
+    pub tick: u64,
+    pub kind: InputKind,
+}
+
+/// Everything needed to reproduce a run: the seed its track was generated
+/// from, its lever and train settings, and every input together with the simulation
+/// tick it was applied on.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    /// Hash of the presets the track was generated from, see
+    /// [`crate::preset_library::PresetLibrary::hash`].
+    pub presets: u64,
+    pub levers: LeverSettings,
+    pub train: TrainSettings,
+    pub inputs: Vec<ReplayInput>,
+    pub crash_tick: Option<u64>,
+    pub crash_cause: Option<DeathCause>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "{}", error),
+            ReplayError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64, presets: u64, levers: LeverSettings, train: TrainSettings) -> Self {
+        Self {
+            seed,
+            presets,
+            levers,
+            train,
+            inputs: Vec::new(),
+            crash_tick: None,
+            crash_cause: None,
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, inputs: Inputs) {
+        if inputs.select_left {
+            self.inputs.push(ReplayInput {
+                tick,
+                kind: InputKind::SelectLeft,
+            });
+        } else if inputs.select_right {
+            self.inputs.push(ReplayInput {
+                tick,
+                kind: InputKind::SelectRight,
+            });
+        }
+
+        if inputs.pull_lever {
+            self.inputs.push(ReplayInput {
+                tick,
+                kind: InputKind::PullLever,
+            });
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ReplayError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let parse_error = |line: usize, message: String| ReplayError::Parse { line, message };
+
+        let (line, header) = lines
+            .next()
+            .ok_or_else(|| parse_error(1, "empty replay file".to_owned()))?;
+
+        let version = header
+            .strip_prefix(REPLAY_HEADER)
+            .map(str::trim)
+            .ok_or_else(|| parse_error(line, "not a replay file".to_owned()))?;
+
+        if version != REPLAY_VERSION.to_string() {
+            return Err(parse_error(
+                line,
+                format!(
+                    "unsupported replay version '{}', expected {}",
+                    version, REPLAY_VERSION
+                ),
+            ));
+        }
+
+        let mut seed = None;
+        let mut presets = None;
+        let mut levers = LeverSettings::default();
+        let mut train = TrainSettings::default();
+        let mut replay_inputs = Vec::new();
+        let mut crash_tick = None;
+        let mut crash_cause = None;
+
+        for (line, content) in lines {
+            let fields: Vec<&str> = content.split_whitespace().collect();
+
+            let parse_tick = |field: &str| {
+                field
+                    .parse::<u64>()
+                    .map_err(|_| parse_error(line, format!("invalid tick '{}'", field)))
+            };
+
+            let parse_count = |field: &str| {
+                field
+                    .parse::<usize>()
+                    .map_err(|_| parse_error(line, format!("invalid count '{}'", field)))
+            };
+
+            match fields.as_slice() {
+                ["seed", value] => {
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| parse_error(line, format!("invalid seed '{}'", value)))?,
+                    );
+                }
+                ["presets", value] => {
+                    presets = Some(u64::from_str_radix(value, 16).map_err(|_| {
+                        parse_error(line, format!("invalid presets hash '{}'", value))
+                    })?);
+                }
+                ["levers", start, max] => {
+                    levers = LeverSettings::new(parse_count(start)?, parse_count(max)?)
+                        .with_targeting(levers.targeting);
+                }
+                ["targeting", name] => {
+                    let targeting = LeverTargeting::from_name(name).ok_or_else(|| {
+                        parse_error(line, format!("unknown lever targeting '{}'", name))
+                    })?;
+
+                    levers = levers.with_targeting(targeting);
+                }
+                ["train", cars, max_cars] => {
+                    train = TrainSettings::new(parse_count(cars)?, parse_count(max_cars)?);
+                }
+                ["input", tick, name] => {
+                    let kind = InputKind::from_name(name)
+                        .ok_or_else(|| parse_error(line, format!("unknown input '{}'", name)))?;
+
+                    let tick = parse_tick(tick)?;
+
+                    if replay_inputs
+                        .last()
+                        .is_some_and(|previous: &ReplayInput| previous.tick > tick)
+                    {
+                        return Err(parse_error(
+                            line,
+                            format!("input at tick {} is out of order", tick),
+                        ));
+                    }
+
+                    replay_inputs.push(ReplayInput { tick, kind });
+                }
+                ["crash", tick, cause] => {
+                    crash_tick = Some(parse_tick(tick)?);
+                    crash_cause = Some(DeathCause::from_name(cause).ok_or_else(|| {
+                        parse_error(line, format!("unknown death cause '{}'", cause))
+                    })?);
+                }
+                _ => return Err(parse_error(line, format!("unexpected entry '{}'", content))),
+            }
+        }
+
+        Ok(Self {
+            seed: seed.ok_or_else(|| parse_error(line, "missing seed".to_owned()))?,
+            presets: presets.ok_or_else(|| parse_error(line, "missing presets hash".to_owned()))?,
+            levers,
+            train,
+            inputs: replay_inputs,
+            crash_tick,
+            crash_cause,
+        })
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", REPLAY_HEADER, REPLAY_VERSION)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "presets {:016x}", self.presets)?;
+        writeln!(f, "levers {} {}", self.levers.start, self.levers.max)?;
+        writeln!(f, "targeting {}", self.levers.targeting.name())?;
+        writeln!(f, "train {} {}", self.train.cars, self.train.max_cars)?;
+
+        for input in &self.inputs {
+            writeln!(f, "input {} {}", input.tick, input.kind.name())?;
+        }
+
+        if let Some(tick) = self.crash_tick
+            && let Some(cause) = self.crash_cause
+        {
+            writeln!(f, "crash {} {}", tick, cause.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Feeds a recorded replay's inputs back to the simulation, tick by tick.
+#[derive(Clone, Debug)]
+pub struct Playback {
+    pub replay: Replay,
+    next_input: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next_input: 0,
+        }
+    }
+
+    pub fn inputs_for_tick(&mut self, tick: u64) -> Inputs {
+        let mut inputs = Inputs::default();
+
+        while let Some(input) = self.replay.inputs.get(self.next_input)
+            && input.tick <= tick
+        {
+            match input.kind {
+                InputKind::SelectLeft => inputs.select_left = true,
+                InputKind::SelectRight => inputs.select_right = true,
+                InputKind::PullLever => inputs.pull_lever = true,
+            }
+
+            self.next_input += 1;
+        }
+
+        inputs
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::{VecDeque, vec_deque},
+    iter::Flatten,
+    ops::{Index, IndexMut},
+};
+
+use crate::Block;
+
+/// Identifies a block for as long as it is part of the track. IDs are handed
+/// out in the order blocks are generated and never shift or get reused, even
+/// after a truncate, so dropping blocks leaves every other reference valid.
+pub type BlockId = usize;
+
+/// The blocks of the track, keyed by `BlockId`. Blocks are appended at the
+/// back, and dropped blocks leave a hole until every block before them is
+/// trimmed too, so the store only grows with the live part of the track.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Track {
+    /// Slots for every ID from `first_id` onwards, `None` once dropped.
+    slots: VecDeque<Option<Block>>,
+    first_id: BlockId,
+    /// Number of slots holding a block.
+    len: usize,
+}
+
+impl Track {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The ID the next appended block gets.
+    pub fn next_id(&self) -> BlockId {
+        self.first_id + self.slots.len()
+    }
+
+    /// Number of blocks still on the track.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, id: BlockId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// The block with `id`, unless it was never generated or has been
+    /// trimmed.
+    pub fn get(&self, id: BlockId) -> Option<&Block> {
+        self.slots.get(id.checked_sub(self.first_id)?)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: BlockId) -> Option<&mut Block> {
+        self.slots.get_mut(id.checked_sub(self.first_id)?)?.as_mut()
+    }
+
+    /// Appends `block` and returns its ID.
+    pub fn push(&mut self, block: Block) -> BlockId {
+        let id = self.next_id();
+        self.slots.push_back(Some(block));
+        self.len += 1;
+        id
+    }
+
+    /// Removes the block with `id`, if it is still there.
+    pub fn remove(&mut self, id: BlockId) -> Option<Block> {
+        let block = self.slots.get_mut(id.checked_sub(self.first_id)?)?.take()?;
+        self.len -= 1;
+
+        while let Some(None) = self.slots.front() {
+            self.slots.pop_front();
+            self.first_id += 1;
+        }
+
+        Some(block)
+    }
+
+    /// Drops every block from `id` onwards. Used to undo appending blocks;
+    /// like trimmed ones, their IDs are not handed out again.
+    pub fn truncate(&mut self, id: BlockId) {
+        let keep = id.saturating_sub(self.first_id).min(self.slots.len());
+
+        for slot in self.slots.range_mut(keep..) {
+            if slot.take().is_some() {
+                self.len -= 1;
+            }
+        }
+
+        while let Some(None) = self.slots.front() {
+            self.slots.pop_front();
+            self.first_id += 1;
+        }
+    }
+
+    /// Every block with its ID, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (BlockId, &Block)> {
+        let first_id = self.first_id;
+
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(move |(offset, block)| Some((first_id + offset, block.as_ref()?)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (BlockId, &mut Block)> {
+        let first_id = self.first_id;
+
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(move |(offset, block)| Some((first_id + offset, block.as_mut()?)))
+    }
+
+    /// An empty `BlockMap` with room for every block currently on the track.
+    pub fn block_map<T: Clone>(&self) -> BlockMap<T> {
+        BlockMap {
+            first_id: self.first_id,
+            values: vec![None; self.slots.len()],
+        }
+    }
+
+    /// Every block, oldest first.
+    pub fn blocks(&self) -> impl DoubleEndedIterator<Item = &Block> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn blocks_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Block> {
+        self.slots.iter_mut().flatten()
+    }
+}
+
+/// A value for some of the blocks of a `Track`, stored densely by ID. Blocks
+/// appended to the track after the map was made have no value.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockMap<T> {
+    first_id: BlockId,
+    values: Vec<Option<T>>,
+}
+
+impl<T> BlockMap<T> {
+    pub fn get(&self, id: &BlockId) -> Option<&T> {
+        self.values.get(id.checked_sub(self.first_id)?)?.as_ref()
+    }
+
+    pub fn contains_key(&self, id: &BlockId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Sets the value of block `id`, which must have been on the track when
+    /// the map was made.
+    pub fn insert(&mut self, id: BlockId, value: T) {
+        self.values[id - self.first_id] = Some(value);
+    }
+}
+
+impl<T> Index<&BlockId> for BlockMap<T> {
+    type Output = T;
+
+    fn index(&self, id: &BlockId) -> &T {
+        self.get(id)
+            .unwrap_or_else(|| panic!("block {} has no value", id))
+    }
+}
+
+impl Index<BlockId> for Track {
+    type Output = Block;
+
+    fn index(&self, id: BlockId) -> &Block {
+        self.get(id)
+            .unwrap_or_else(|| panic!("block {} is not on the track", id))
+    }
+}
+
+impl IndexMut<BlockId> for Track {
+    fn index_mut(&mut self, id: BlockId) -> &mut Block {
+        self.get_mut(id)
+            .unwrap_or_else(|| panic!("block {} is not on the track", id))
+    }
+}
+
+impl<'a> IntoIterator for &'a Track {
+    type Item = &'a Block;
+    type IntoIter = Flatten<vec_deque::Iter<'a, Option<Block>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slots.iter().flatten()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Track {
+    type Item = &'a mut Block;
+    type IntoIter = Flatten<vec_deque::IterMut<'a, Option<Block>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slots.iter_mut().flatten()
+    }
+}
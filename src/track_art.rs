@@ -0,0 +1,351 @@
+use macroquad::{
+    color::Color,
+    math::{Rect, Vec2},
+    models::{Mesh, Vertex},
+};
+
+use crate::{Block, RailRef, World, track::BlockId};
+
+/// Distance between the two rails of a track.
+pub const GAUGE: f32 = 24.0;
+pub const RAIL_WIDTH: f32 = 3.5;
+/// Width of the gravel bed under the sleepers.
+pub const BALLAST_WIDTH: f32 = 50.0;
+pub const SLEEPER_SPACING: f32 = 12.0;
+pub const SLEEPER_LENGTH: f32 = 38.0;
+pub const SLEEPER_WIDTH: f32 = 5.0;
+/// How far the movable blades of a switch reach into each fork branch.
+pub const BLADE_LENGTH: f32 = 26.0;
+
+/// Opacity of the branches a train would not take: unselected fork branches
+/// and spurs behind a closed gate.
+pub const UNUSED_OPACITY: f32 = 0.45;
+
+pub const BALLAST_COLOR: Color = Color::new(0.24, 0.22, 0.2, 1.0);
+pub const SLEEPER_COLOR: Color = Color::new(0.42, 0.3, 0.2, 1.0);
+pub const RAIL_COLOR: Color = Color::new(0.78, 0.8, 0.85, 1.0);
+pub const BLADE_COLOR: Color = Color::new(1.0, 0.85, 0.2, 1.0);
+pub const BUFFER_STOP_COLOR: Color = Color::new(0.9, 0.15, 0.15, 1.0);
+pub const OPEN_GATE_COLOR: Color = Color::new(0.2, 0.85, 0.3, 1.0);
+
+/// Most indices macroquad draws in one call by default. Larger meshes get cut
+/// off, so art is split over as many meshes as it takes.
+pub const MAX_MESH_INDICES: usize = 5000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ArtTriangle {
+    pub corners: [Vec2; 3],
+    pub color: Color,
+}
+
+/// Track drawn as flat-coloured triangles in world space, back to front.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TrackArt {
+    pub triangles: Vec<ArtTriangle>,
+}
+
+impl TrackArt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The gravel bed along `curve`.
+    pub fn ballast(&mut self, curve: &[Vec2], opacity: f32) {
+        self.band(curve, BALLAST_WIDTH, with_opacity(BALLAST_COLOR, opacity));
+    }
+
+    /// Sleepers across `curve`, evenly spaced along it.
+    pub fn sleepers(&mut self, curve: &[Vec2], opacity: f32) {
+        let color = with_opacity(SLEEPER_COLOR, opacity);
+
+        for (center, direction) in points_along(curve, SLEEPER_SPACING) {
+            let across = direction.perp() * SLEEPER_LENGTH / 2.0;
+            let along = direction * SLEEPER_WIDTH / 2.0;
+
+            self.quad(
+                [
+                    center - across - along,
+                    center - across + along,
+                    center + across + along,
+                    center + across - along,
+                ],
+                color,
+            );
+        }
+    }
+
+    /// Both rails, on either side of `curve`.
+    pub fn rails(&mut self, curve: &[Vec2], opacity: f32) {
+        let color = with_opacity(RAIL_COLOR, opacity);
+
+        for side in [-1.0, 1.0] {
+            self.band(&offset_curve(curve, side * GAUGE / 2.0), RAIL_WIDTH, color);
+        }
+    }
+
+    /// The blades at the start of a fork branch, tapering to a point where
+    /// the branches split. Blades set for the branch the train takes stand
+    /// out.
+    pub fn switch_blades(&mut self, curve: &[Vec2], selected: bool) {
+        let color = if selected {
+            BLADE_COLOR
+        } else {
+            with_opacity(RAIL_COLOR, UNUSED_OPACITY)
+        };
+
+        for side in [-1.0, 1.0] {
+            let rail = offset_curve(curve, side * GAUGE / 2.0);
+            let (heel, direction) = point_at(&rail, BLADE_LENGTH);
+            let across = direction.perp() * RAIL_WIDTH;
+
+            self.triangle([rail[0], heel - across, heel + across], color);
+        }
+    }
+
+    /// A buffer stop closing off the end of `curve`.
+    pub fn buffer_stop(&mut self, curve: &[Vec2], opacity: f32) {
+        let color = with_opacity(BUFFER_STOP_COLOR, opacity);
+        let (end, direction) = end_of(curve);
+        let across = direction.perp();
+
+        // The beam, with a strut back along each rail.
+        self.bar(end, across, GAUGE + 12.0, 6.0, color);
+
+        for side in [-1.0, 1.0] {
+            let foot = end + across * side * GAUGE / 2.0;
+
+            self.triangle(
+                [
+                    foot - across * 2.5,
+                    foot + across * 2.5,
+                    foot - direction * 12.0,
+                ],
+                color,
+            );
+        }
+    }
+
+    /// The gate at the end of a merge spur: a bar across the track while
+    /// closed, swung alongside it while open.
+    pub fn gate(&mut self, curve: &[Vec2], open: bool) {
+        let (end, direction) = end_of(curve);
+        let across = direction.perp();
+
+        if open {
+            let hinge = end + across * (GAUGE / 2.0 + 6.0);
+            self.bar(
+                hinge - direction * 8.0,
+                direction,
+                GAUGE + 4.0,
+                3.0,
+                OPEN_GATE_COLOR,
+            );
+        } else {
+            self.bar(end, across, GAUGE + 12.0, 3.0, BUFFER_STOP_COLOR);
+        }
+    }
+
+    /// A rectangle `length` long along `direction` and `width` wide,
+    /// centered on `center`.
+    fn bar(&mut self, center: Vec2, direction: Vec2, length: f32, width: f32, color: Color) {
+        let along = direction * length / 2.0;
+        let across = direction.perp() * width / 2.0;
+
+        self.quad(
+            [
+                center - along - across,
+                center + along - across,
+                center + along + across,
+                center - along + across,
+            ],
+            color,
+        );
+    }
+
+    /// A strip `width` wide along `line`.
+    fn band(&mut self, line: &[Vec2], width: f32, color: Color) {
+        let left = offset_curve(line, width / 2.0);
+        let right = offset_curve(line, -width / 2.0);
+
+        for i in 1..line.len() {
+            self.quad([left[i - 1], left[i], right[i], right[i - 1]], color);
+        }
+    }
+
+    fn quad(&mut self, corners: [Vec2; 4], color: Color) {
+        self.triangle([corners[0], corners[1], corners[2]], color);
+        self.triangle([corners[0], corners[2], corners[3]], color);
+    }
+
+    fn triangle(&mut self, corners: [Vec2; 3], color: Color) {
+        self.triangles.push(ArtTriangle { corners, color });
+    }
+
+    /// The triangles as meshes of at most `MAX_MESH_INDICES` indices, each
+    /// drawn in a single call, in order.
+    pub fn to_meshes(&self) -> Vec<Mesh> {
+        self.triangles
+            .chunks(MAX_MESH_INDICES / 3)
+            .map(|triangles| {
+                let vertices: Vec<Vertex> = triangles
+                    .iter()
+                    .flat_map(|triangle| {
+                        triangle.corners.map(|corner| {
+                            Vertex::new(corner.x, corner.y, 0.0, 0.0, 0.0, triangle.color)
+                        })
+                    })
+                    .collect();
+
+                Mesh {
+                    indices: (0..vertices.len() as u16).collect(),
+                    vertices,
+                    texture: None,
+                }
+            })
+            .collect()
+    }
+
+    /// The smallest rectangle holding every triangle.
+    pub fn bounds(&self) -> Rect {
+        let mut corners = self.triangles.iter().flat_map(|triangle| triangle.corners);
+        let first = corners.next().unwrap_or_default();
+        let (min, max) = corners.fold((first, first), |(min, max), corner| {
+            (min.min(corner), max.max(corner))
+        });
+
+        Rect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+}
+
+/// The art for the block `id`, showing which way its fork is set and whether
+/// its gate is open.
+pub fn block_art(world: &World, id: BlockId) -> TrackArt {
+    let block = &world.track[id];
+    let rails = block.rails();
+
+    // Rails the train would not take go first, so the ones it would are
+    // drawn on top where they overlap.
+    let mut curves: Vec<(usize, Vec<Vec2>, f32)> = (0..rails.len())
+        .map(|branch| {
+            let in_use = match block {
+                Block::Rail(_) => true,
+                Block::Fork(fork) => branch == fork.selected,
+                Block::Merge(merge) => branch == 0 || merge.gate.is_none_or(|gate| gate.open),
+            };
+            let opacity = if in_use { 1.0 } else { UNUSED_OPACITY };
+
+            (
+                branch,
+                world.rail_curve(RailRef { block: id, branch }),
+                opacity,
+            )
+        })
+        .collect();
+    curves.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let mut art = TrackArt::new();
+
+    for (_, curve, opacity) in &curves {
+        art.ballast(curve, *opacity);
+    }
+
+    for (_, curve, opacity) in &curves {
+        art.sleepers(curve, *opacity);
+    }
+
+    for (_, curve, opacity) in &curves {
+        art.rails(curve, *opacity);
+    }
+
+    for (branch, curve, opacity) in &curves {
+        if let Block::Fork(fork) = block {
+            art.switch_blades(curve, *branch == fork.selected);
+        }
+
+        if rails[*branch].is_wall {
+            art.buffer_stop(curve, *opacity);
+        }
+
+        if let Block::Merge(merge) = block
+            && *branch == 1
+            && let Some(gate) = merge.gate
+        {
+            art.gate(curve, gate.open);
+        }
+    }
+
+    art
+}
+
+/// `curve` moved sideways by `offset`, to the left of the direction of travel
+/// for positive offsets.
+pub fn offset_curve(curve: &[Vec2], offset: f32) -> Vec<Vec2> {
+    (0..curve.len())
+        .map(|i| {
+            let before = curve[i.saturating_sub(1)];
+            let after = curve[(i + 1).min(curve.len() - 1)];
+
+            curve[i] + (after - before).normalize_or_zero().perp() * offset
+        })
+        .collect()
+}
+
+/// Points `spacing` apart along `curve`, starting half a spacing in, each
+/// with the direction of the curve there.
+pub fn points_along(curve: &[Vec2], spacing: f32) -> Vec<(Vec2, Vec2)> {
+    let mut points = Vec::new();
+    let mut next = spacing / 2.0;
+    let mut travelled = 0.0;
+
+    for pair in curve.windows(2) {
+        let length = pair[0].distance(pair[1]);
+        let direction = (pair[1] - pair[0]).normalize_or_zero();
+
+        while next <= travelled + length {
+            points.push((pair[0] + direction * (next - travelled), direction));
+            next += spacing;
+        }
+
+        travelled += length;
+    }
+
+    points
+}
+
+/// The point `distance` along `line`, clamped to its end, with the
+/// direction of the line there.
+fn point_at(line: &[Vec2], distance: f32) -> (Vec2, Vec2) {
+    let mut remaining = distance;
+
+    for pair in line.windows(2) {
+        let length = pair[0].distance(pair[1]);
+        let direction = (pair[1] - pair[0]).normalize_or_zero();
+
+        if remaining <= length {
+            return (pair[0] + direction * remaining, direction);
+        }
+
+        remaining -= length;
+    }
+
+    end_of(line)
+}
+
+/// The last point of `line`, with the direction it ends in.
+fn end_of(line: &[Vec2]) -> (Vec2, Vec2) {
+    let end = line[line.len() - 1];
+    let direction = line
+        .iter()
+        .rev()
+        .find(|point| **point != end)
+        .map_or(Vec2::X, |before| (end - *before).normalize());
+
+    (end, direction)
+}
+
+fn with_opacity(color: Color, opacity: f32) -> Color {
+    Color {
+        a: color.a * opacity,
+        ..color
+    }
+}
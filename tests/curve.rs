@@ -0,0 +1,62 @@
+use d2j2025::curve::{CURVE_SAMPLES, CurveSegment, smooth_points};
+use macroquad::math::{Vec2, vec2};
+
+/// Points 30 apart on a circle, turning by `step` radians per point.
+fn arc(step: f32, count: usize) -> Vec<Vec2> {
+    let mut points = vec![Vec2::ZERO];
+
+    for i in 1..count {
+        points.push(points[i - 1] + 30.0 * Vec2::from_angle(i as f32 * step));
+    }
+
+    points
+}
+
+#[test]
+fn segments_run_from_start_to_end() {
+    let segment = CurveSegment::new(
+        Some(vec2(-30.0, 5.0)),
+        vec2(0.0, 0.0),
+        vec2(30.0, 0.0),
+        Some(vec2(58.0, 10.0)),
+    );
+
+    assert_eq!(segment.position(0.0), segment.start);
+    assert!(segment.position(1.0).distance(segment.end) < 1e-4);
+    assert_eq!(segment.at_distance(0.0).0, segment.start);
+    assert!(segment.at_distance(1000.0).0.distance(segment.end) < 1e-4);
+    assert!(segment.length() >= 30.0);
+}
+
+#[test]
+fn straight_segments_keep_their_length() {
+    let segment = CurveSegment::new(None, vec2(0.0, 0.0), vec2(30.0, 0.0), None);
+
+    assert!((segment.length() - 30.0).abs() < 1e-4);
+
+    let (position, heading) = segment.at_distance(12.0);
+    assert!(position.distance(vec2(12.0, 0.0)) < 1e-4);
+    assert!(heading.abs() < 1e-4);
+}
+
+#[test]
+fn smooth_curves_pass_through_every_point_without_kinks() {
+    let points = arc(0.3, 6);
+    let smooth = smooth_points(&points, None, None);
+
+    assert_eq!(smooth.len(), (points.len() - 1) * CURVE_SAMPLES + 1);
+
+    for (i, point) in points.iter().enumerate() {
+        assert!(smooth[i * CURVE_SAMPLES].distance(*point) < 1e-3);
+    }
+
+    // Headings change by small steps all along, including across points.
+    for pieces in smooth.windows(3) {
+        let turn = (pieces[1] - pieces[0]).angle_between(pieces[2] - pieces[1]);
+        assert!(
+            turn.abs() < 0.3 / CURVE_SAMPLES as f32 * 2.0,
+            "turn {}",
+            turn
+        );
+    }
+}
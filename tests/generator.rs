@@ -0,0 +1,204 @@
+use std::rc::Rc;
+
+use d2j2025::{
+    Block, World,
+    game::{MIN_MS_TO_NEXT_POINT, START_MS_TO_NEXT_POINT},
+    get_last_rail_world_position,
+    preset::{collides_with_track, preset_random, spawn_preset},
+    preset_library::{PresetLibrary, STARTING_PRESET},
+};
+
+fn new_world(seed: u64) -> World {
+    new_world_with(seed, Rc::new(PresetLibrary::builtin()))
+}
+
+fn new_world_with(seed: u64, presets: Rc<PresetLibrary>) -> World {
+    let mut world = World::new(seed, presets);
+
+    for _ in 0..4 {
+        spawn_preset(&mut world, STARTING_PRESET);
+    }
+
+    world
+}
+
+/// Speed at the `i`th of `n` pieces, going from the start speed to top speed.
+fn ms_to_next_point(i: usize, n: usize) -> f32 {
+    START_MS_TO_NEXT_POINT - (START_MS_TO_NEXT_POINT - MIN_MS_TO_NEXT_POINT) * i as f32 / n as f32
+}
+
+/// Points the train passes from the start of the previous fork or gate on
+/// its way with the same letter as the block at `index` to the start of that
+/// block.
+fn points_from_previous_fork(world: &World, index: usize) -> Option<usize> {
+    let letter = world.track[index].letter();
+
+    let mut points = 0;
+    let mut block = index;
+
+    loop {
+        let parent = world.parent(block)?;
+        points += world.rail(parent).points.len() - 1;
+
+        if world.track[parent.block].letter() == letter {
+            return Some(points);
+        }
+
+        block = parent.block;
+    }
+}
+
+/// Generates `pieces` presets on each seed and checks that every new fork
+/// leaves the library's reaction time after the previous one with its letter.
+/// Returns how many forks were checked.
+fn check_fork_gaps(presets: Rc<PresetLibrary>, seeds: u64, pieces: usize) -> usize {
+    let mut forks = 0;
+
+    for seed in 0..seeds {
+        let mut world = new_world_with(seed, presets.clone());
+
+        for i in 0..pieces {
+            let first_new_block = world.track.next_id();
+            let ms_to_next_point = ms_to_next_point(i, pieces);
+
+            preset_random(&mut world, i as f32 / pieces as f32, ms_to_next_point, true);
+
+            let added: Vec<usize> = world
+                .track
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| *id >= first_new_block)
+                .collect();
+
+            for index in added {
+                if world.track[index].letter().is_none() {
+                    continue;
+                }
+
+                forks += 1;
+
+                if let Some(points) = points_from_previous_fork(&world, index) {
+                    assert!(
+                        points as f32 * ms_to_next_point >= presets.min_reaction_ms,
+                        "seed {}: fork {} is only {} points after the last one at {} ms per point",
+                        seed,
+                        index,
+                        points,
+                        ms_to_next_point
+                    );
+                }
+            }
+        }
+    }
+
+    forks
+}
+
+fn is_single_straight(blocks: &[&Block]) -> bool {
+    matches!(blocks, [Block::Rail(rail)] if (rail.last_angle() - rail.points[1].to_angle()).abs() < 1e-4)
+}
+
+#[test]
+fn generated_pieces_keep_clear_of_the_track() {
+    let mut pieces = 0;
+    let mut fallbacks = 0;
+
+    for seed in 0..200 {
+        let mut world = new_world(seed);
+
+        for i in 0..60 {
+            let first_new_block = world.track.next_id();
+            let junction = get_last_rail_world_position(&world);
+
+            preset_random(&mut world, i as f32 / 60.0, ms_to_next_point(i, 60), true);
+            pieces += 1;
+
+            if collides_with_track(&world, first_new_block, junction) {
+                // Only the fallback straight may be placed without room.
+                let added: Vec<&Block> = world
+                    .track
+                    .iter()
+                    .filter(|(id, _)| *id >= first_new_block)
+                    .map(|(_, block)| block)
+                    .collect();
+
+                assert!(is_single_straight(&added));
+                fallbacks += 1;
+            }
+        }
+    }
+
+    // The track can still box itself in, but that should stay rare.
+    assert!(
+        fallbacks * 100 < pieces * 8,
+        "{} of {} pieces needed the fallback",
+        fallbacks,
+        pieces
+    );
+}
+
+#[test]
+fn forks_leave_time_to_react() {
+    let forks = check_fork_gaps(Rc::new(PresetLibrary::builtin()), 2000, 40);
+
+    assert!(forks > 10000);
+}
+
+/// Presets where every fork shows the letter A and nothing else is ever
+/// picked, so only padding straights can keep the forks apart.
+fn fixed_letter_presets(min_reaction_ms: f32) -> Rc<PresetLibrary> {
+    let text = format!(
+        r#"
+min_reaction_ms = {:?}
+
+[rails.straight]
+spacing = 30.0
+points = 8
+angle_step_degrees = 0.0
+
+[rails.turn]
+spacing = 30.0
+points = 5
+angle_step_degrees = 22.5
+
+[[preset]]
+name = "straight"
+weight = 0.0
+blocks = [{{ rail = "straight" }}]
+
+[[preset]]
+name = "fork"
+weight = 1.0
+blocks = [{{ fork = [{{ rail = "turn" }}, {{ rail = "turn", mirrored = true }}], walls = 1, letter = "A" }}]
+"#,
+        min_reaction_ms
+    );
+
+    Rc::new(PresetLibrary::parse(&text).unwrap())
+}
+
+#[test]
+fn fixed_letter_forks_are_padded() {
+    let forks = check_fork_gaps(fixed_letter_presets(900.0), 1000, 20);
+
+    assert!(forks > 10000);
+}
+
+#[test]
+fn padding_in_front_of_forks_is_capped() {
+    // No gap is ever long enough, so padding alone never makes a fork fair.
+    let mut world = new_world_with(0, fixed_letter_presets(1000000000.0));
+
+    for _ in 0..20 {
+        let first_new_block = world.track.next_id();
+
+        preset_random(&mut world, 0.0, MIN_MS_TO_NEXT_POINT, true);
+
+        let added = world
+            .track
+            .iter()
+            .filter(|(id, _)| *id >= first_new_block)
+            .count();
+        assert!(added <= 9, "{} blocks added for one fork", added);
+    }
+}
@@ -0,0 +1,284 @@
+use std::rc::Rc;
+
+use d2j2025::{
+    Block, Letter,
+    game::{Game, Inputs, LeverSettings},
+    preset_library::{PresetError, PresetLibrary},
+};
+
+const MINIMAL_PRESETS: &str = r#"
+[rails.straight]
+spacing = 30.0
+points = 8
+angle_step_degrees = 0.0
+
+[rails.turn]
+spacing = 20.0
+points = 4
+angle_step_degrees = 30.0
+
+[[preset]]
+name = "straight"
+weight = 1.0
+blocks = [{ rail = "straight" }]
+
+[[preset]]
+name = "u_turn"
+weight = 1.0
+blocks = [{ rail = "turn" }]
+
+[[preset]]
+name = "turn_90"
+weight = 1.0
+blocks = [{ rail = "turn", mirror = "always" }]
+
+[[preset]]
+name = "fork_turn_90"
+weight = 1.0
+blocks = [{ fork = [{ rail = "turn" }, { rail = "turn", wall = true }], letter = "B" }]
+
+[[preset]]
+name = "fork_u_turn"
+weight = 1.0
+blocks = [{ fork = [{ rail = "turn" }, { rail = "turn" }], walls = 1, letter = "B" }]
+"#;
+
+fn error_line(text: &str) -> usize {
+    match PresetLibrary::parse(text) {
+        Err(PresetError::Invalid { line, .. }) => line,
+        other => panic!("expected an invalid preset error, got {:?}", other),
+    }
+}
+
+#[test]
+fn builtin_presets_parse() {
+    let library = PresetLibrary::builtin();
+
+    assert!(library.get("straight").is_some());
+    assert_eq!(
+        PresetLibrary::load("presets.toml").unwrap(),
+        library,
+        "the shipped presets.toml should match the embedded copy"
+    );
+}
+
+#[test]
+fn custom_presets_drive_generation() {
+    let presets = Rc::new(PresetLibrary::parse(MINIMAL_PRESETS).unwrap());
+    let mut game = Game::with_seed(1).with_presets(presets);
+
+    game.step(0.0, Inputs::default());
+
+    let mut forks = 0;
+
+    for block in &game.world.track {
+        match block {
+            Block::Rail(rail) => assert!(rail.points.len() == 8 || rail.points.len() == 4),
+            Block::Fork(fork) => {
+                forks += 1;
+                assert_eq!(fork.letter, Letter::B);
+                assert_eq!(fork.branches.len(), 2);
+                assert_ne!(fork.branches[0].is_wall, fork.branches[1].is_wall);
+                assert_eq!(fork.branches[0].points.len(), 4);
+            }
+            Block::Merge(_) => panic!("no preset contains a merge"),
+        }
+    }
+
+    assert!(forks > 0);
+}
+
+#[test]
+fn fixed_letters_wait_for_their_lever() {
+    let presets = Rc::new(
+        PresetLibrary::parse(&MINIMAL_PRESETS.replace(r#"letter = "B""#, r#"letter = "E""#))
+            .unwrap(),
+    );
+
+    let game = Game::with_seed(1).with_presets(presets.clone());
+    let letters: Vec<Letter> = game
+        .world
+        .track
+        .blocks()
+        .filter_map(Block::letter)
+        .collect();
+
+    assert!(!letters.is_empty());
+    assert!(letters.iter().all(|letter| letter.index() < 3));
+
+    let game = Game::with_seed(1)
+        .with_presets(presets)
+        .with_levers(LeverSettings::new(5, 5));
+
+    assert!(
+        game.world
+            .track
+            .blocks()
+            .filter_map(Block::letter)
+            .all(|letter| letter == Letter::E)
+    );
+}
+
+#[test]
+fn malformed_presets_report_line_numbers() {
+    let unknown_rail = MINIMAL_PRESETS.replace(
+        r#"name = "u_turn"
+weight = 1.0
+blocks = [{ rail = "turn" }]"#,
+        r#"name = "u_turn"
+weight = 1.0
+blocks = [{ rail = "loop" }]"#,
+    );
+    assert_eq!(error_line(&unknown_rail), 20);
+
+    let bad_mirror = MINIMAL_PRESETS.replace(r#"mirror = "always""#, r#"mirror = "sometimes""#);
+    assert_eq!(error_line(&bad_mirror), 25);
+
+    let too_few_points = MINIMAL_PRESETS.replace("points = 4", "points = 1");
+    assert_eq!(error_line(&too_few_points), 7);
+
+    let no_open_branch = MINIMAL_PRESETS.replace("walls = 1", "walls = 2");
+    assert_eq!(error_line(&no_open_branch), 35);
+
+    let unknown_letter = MINIMAL_PRESETS.replace(r#"letter = "B""#, r#"letter = "G""#);
+    assert_eq!(error_line(&unknown_letter), 30);
+
+    let syntax_error = MINIMAL_PRESETS.replace("spacing = 20.0", "spacing = ");
+    assert_eq!(error_line(&syntax_error), 8);
+
+    let negative_weight = MINIMAL_PRESETS.replacen("weight = 1.0", "weight = -1.0", 2);
+    assert_eq!(error_line(&negative_weight), 12);
+
+    let forked_padding = MINIMAL_PRESETS.replace(
+        r#"blocks = [{ rail = "straight" }]"#,
+        r#"blocks = [{ fork = [{ rail = "turn" }, { rail = "turn", wall = true }] }]"#,
+    );
+    assert_eq!(error_line(&forked_padding), 12);
+
+    let gated_rail = MINIMAL_PRESETS.replace(
+        r#"blocks = [{ rail = "turn" }]"#,
+        r#"blocks = [{ rail = "turn", gate = "A" }]"#,
+    );
+    assert_eq!(error_line(&gated_rail), 20);
+
+    let negative_reaction = format!("min_reaction_ms = -1.0\n{}", MINIMAL_PRESETS);
+    assert_eq!(error_line(&negative_reaction), 1);
+
+    let missing_preset = MINIMAL_PRESETS.replace(r#"name = "straight""#, r#"name = "plain""#);
+    assert!(
+        PresetLibrary::parse(&missing_preset)
+            .unwrap_err()
+            .to_string()
+            .contains("straight")
+    );
+}
+
+#[test]
+fn merge_spurs_must_head_forwards() {
+    let merge = |text: &str| {
+        text.replace(
+            r#"blocks = [{ rail = "turn" }]"#,
+            r#"blocks = [{ merge = "turn" }]"#,
+        )
+    };
+
+    // Three 30 degree steps end up heading across the main line.
+    assert_eq!(error_line(&merge(MINIMAL_PRESETS)), 20);
+
+    let gentle = MINIMAL_PRESETS.replace("angle_step_degrees = 30.0", "angle_step_degrees = 20.0");
+    assert!(PresetLibrary::parse(&merge(&gentle)).is_ok());
+}
+
+#[test]
+fn distribution_follows_difficulty() {
+    let library = PresetLibrary::builtin();
+
+    for difficulty in [0.0, 0.25, 0.5, 1.0] {
+        let total: f32 = library
+            .distribution(difficulty)
+            .iter()
+            .map(|(_, probability)| probability)
+            .sum();
+
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+
+    let fork_chance = |difficulty| -> f32 {
+        library
+            .distribution(difficulty)
+            .iter()
+            .filter(|(name, _)| name.starts_with("fork"))
+            .map(|(_, probability)| probability)
+            .sum()
+    };
+
+    assert!(fork_chance(1.0) > fork_chance(0.5));
+    assert!(fork_chance(0.5) > fork_chance(0.0));
+}
+
+#[test]
+fn zero_weight_presets_are_never_picked() {
+    let text = MINIMAL_PRESETS.replace(
+        "name = \"u_turn\"\nweight = 1.0",
+        "name = \"u_turn\"\nweight = 0.0\nhard_weight = 5.0",
+    );
+    let library = PresetLibrary::parse(&text).unwrap();
+
+    assert_eq!(library.distribution(0.0)[1], ("u_turn", 0.0));
+    assert!(library.distribution(1.0)[1].1 > 0.5);
+}
+
+#[test]
+fn three_way_forks_leave_one_open_branch() {
+    let text = MINIMAL_PRESETS.replace(
+        r#"blocks = [{ fork = [{ rail = "turn" }, { rail = "turn" }], walls = 1, letter = "B" }]"#,
+        r#"blocks = [{ fork = [{ rail = "turn" }, { rail = "straight" }, { rail = "turn", mirrored = true }], walls = 2, letter = "B" }]"#,
+    );
+    let presets = Rc::new(PresetLibrary::parse(&text).unwrap());
+    let mut game = Game::with_seed(3).with_presets(presets);
+
+    game.step(0.0, Inputs::default());
+
+    let mut three_way_forks = 0;
+
+    for block in &mut game.world.track {
+        if let Block::Fork(fork) = block
+            && fork.branches.len() == 3
+        {
+            three_way_forks += 1;
+
+            let open: Vec<usize> = (0..3).filter(|&i| !fork.branches[i].is_wall).collect();
+            assert_eq!(open.len(), 1);
+
+            // Pulling the lever visits every branch before coming back.
+            let start = fork.selected;
+            let mut visited = vec![start];
+
+            for _ in 0..3 {
+                fork.cycle();
+                visited.push(fork.selected);
+            }
+
+            assert_eq!(visited.last(), Some(&start));
+            visited.sort();
+            visited.dedup();
+            assert_eq!(visited, [0, 1, 2]);
+        }
+    }
+
+    assert!(three_way_forks > 0);
+}
+
+#[test]
+fn splitting_forks_end_their_preset() {
+    let split = MINIMAL_PRESETS.replace("walls = 1", "walls = 0");
+    let library = PresetLibrary::parse(&split).unwrap();
+    assert!(library.get("fork_u_turn").unwrap().splits());
+    assert!(!library.get("fork_turn_90").unwrap().splits());
+
+    let split_in_middle = split.replace(
+        r#"walls = 0, letter = "B" }]"#,
+        r#"walls = 0, letter = "B" }, { rail = "straight" }]"#,
+    );
+    assert_eq!(error_line(&split_in_middle), 35);
+}
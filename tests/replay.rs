@@ -0,0 +1,162 @@
+use d2j2025::{
+    game::{Game, GameEvent, Inputs, LeverSettings, LeverTargeting, TrainSettings},
+    replay::{REPLAY_VERSION, Replay, ReplayError},
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+fn record_random_run(seed: u64, input_seed: u64) -> Game {
+    record_random_run_with_levers(seed, input_seed, LeverSettings::default())
+}
+
+fn record_random_run_with_levers(seed: u64, input_seed: u64, levers: LeverSettings) -> Game {
+    let mut rng = StdRng::seed_from_u64(input_seed);
+    let mut game = Game::with_seed(seed)
+        .with_levers(levers)
+        .with_train(TrainSettings::new(4, 9));
+
+    for _ in 0..100_000 {
+        let inputs = Inputs {
+            select_left: rng.random_bool(0.05),
+            select_right: rng.random_bool(0.05),
+            pull_lever: rng.random_bool(0.1),
+            restart: false,
+        };
+
+        // Irregular frame lengths, as a real frame loop would produce.
+        let dt = rng.random_range(0.001..0.05);
+
+        if game.step(dt, inputs).contains(&GameEvent::Crashed) {
+            break;
+        }
+    }
+
+    game
+}
+
+#[test]
+fn replay_reproduces_run_and_crash_point() {
+    for seed in 0..20 {
+        let recorded = record_random_run(seed, seed + 1000);
+        assert!(recorded.recording.crash_tick.is_some());
+
+        let text = recorded.recording.to_string();
+        let replay = Replay::parse(&text).unwrap();
+        assert_eq!(replay, recorded.recording);
+
+        let mut replayed = Game::from_replay(replay);
+
+        for _ in 0..1_000_000 {
+            if replayed
+                .step(1.0 / 60.0, Inputs::default())
+                .contains(&GameEvent::Crashed)
+            {
+                break;
+            }
+        }
+
+        assert_eq!(replayed.recording, recorded.recording);
+        assert_eq!(
+            replayed.state.points_traversed,
+            recorded.state.points_traversed
+        );
+        assert_eq!(replayed.state.survival_ms, recorded.state.survival_ms);
+        assert_eq!(replayed.world.track, recorded.world.track);
+    }
+}
+
+#[test]
+fn replay_ignores_live_inputs() {
+    let recorded = record_random_run(5, 6);
+    let mut replayed = Game::from_replay(recorded.recording.clone());
+
+    let pull_every_frame = Inputs {
+        pull_lever: true,
+        select_right: true,
+        ..Default::default()
+    };
+
+    while replayed.state.alive {
+        replayed.step(1.0 / 60.0, pull_every_frame);
+    }
+
+    assert_eq!(replayed.recording, recorded.recording);
+}
+
+#[test]
+fn replay_keeps_lever_settings() {
+    let levers = LeverSettings::new(2, 6).with_targeting(LeverTargeting::Sticky);
+    let recorded = record_random_run_with_levers(3, 4, levers);
+
+    let replay = Replay::parse(&recorded.recording.to_string()).unwrap();
+    assert_eq!(replay.levers, levers);
+    assert_eq!(replay.train, TrainSettings::new(4, 9));
+
+    let mut replayed = Game::from_replay(replay);
+    assert_eq!(replayed.lever_count, 2);
+
+    while replayed.state.alive {
+        replayed.step(1.0 / 60.0, Inputs::default());
+    }
+
+    assert_eq!(replayed.recording, recorded.recording);
+    assert_eq!(replayed.lever_states(), recorded.lever_states());
+}
+
+/// The first line of a replay of the current version.
+fn header() -> String {
+    format!("railway-panic-replay {}", REPLAY_VERSION)
+}
+
+fn parse_error_line(text: &str) -> usize {
+    match Replay::parse(text) {
+        Err(ReplayError::Parse { line, .. }) => line,
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn malformed_replays_report_line_numbers() {
+    let newer = format!("railway-panic-replay {}\nseed 1\n", REPLAY_VERSION + 1);
+    assert_eq!(parse_error_line(&newer), 1);
+    assert_eq!(parse_error_line("something else\n"), 1);
+    assert_eq!(
+        parse_error_line(&format!("{}\nseed 1\ninput 10 jump\n", header())),
+        3
+    );
+    assert_eq!(
+        parse_error_line(&format!(
+            "{}\nseed 1\n\ninput 10 lever\ninput 5 lever\n",
+            header()
+        )),
+        5
+    );
+    assert_eq!(parse_error_line(&format!("{}\nseed x\n", header())), 2);
+    assert_eq!(
+        parse_error_line(&format!("{}\nseed 1\npresets nothex\n", header())),
+        3
+    );
+    assert_eq!(
+        parse_error_line(&format!("{}\nseed 1\nlevers 3 many\n", header())),
+        3
+    );
+    assert_eq!(
+        parse_error_line(&format!("{}\nseed 1\ntargeting random\n", header())),
+        3
+    );
+    assert_eq!(
+        parse_error_line(&format!("{}\nseed 1\ncrash 40 boredom\n", header())),
+        3
+    );
+}
+
+#[test]
+fn replay_records_death_cause() {
+    let recorded = record_random_run(3, 4);
+    let cause = recorded.state.death_cause;
+
+    assert!(cause.is_some());
+    assert_eq!(recorded.recording.crash_cause, cause);
+
+    let replay = Replay::parse(&recorded.recording.to_string()).unwrap();
+    assert_eq!(replay.crash_cause, cause);
+}
@@ -0,0 +1,1119 @@
+use std::rc::Rc;
+
+use d2j2025::{
+    Block, Crossing, DeathCause, Hazard, Letter, RailRef, World, curve_crossings,
+    game::{
+        CAR_GAP, CAR_LENGTH, Game, GameEvent, Inputs, LOCOMOTIVE_LENGTH, LeverSettings, LeverState,
+        LeverTargeting, ORIGIN_REBASE_DISTANCE, TrainSettings, VISIBLE_BLOCKS,
+    },
+    preset_library::PresetLibrary,
+};
+use macroquad::math::Vec2;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn random_inputs(rng: &mut impl Rng) -> Inputs {
+    Inputs {
+        select_left: rng.random_bool(0.05),
+        select_right: rng.random_bool(0.05),
+        pull_lever: rng.random_bool(0.05),
+        restart: false,
+    }
+}
+
+fn run_until_crash(game: &mut Game, max_frames: usize) -> bool {
+    for _ in 0..max_frames {
+        if game
+            .step(DT, Inputs::default())
+            .contains(&GameEvent::Crashed)
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Routes every fork away from its wall and opens every gate, so the train
+/// never crashes.
+fn autopilot(game: &mut Game) {
+    for block in &mut game.world.track {
+        match block {
+            Block::Rail(_) => {}
+            Block::Fork(fork) => {
+                while fork.selected_rail().is_wall {
+                    fork.cycle();
+                }
+            }
+            Block::Merge(merge) => {
+                if let Some(gate) = &mut merge.gate {
+                    gate.open = true;
+                }
+            }
+        }
+    }
+}
+
+fn run_with_autopilot(seed: u64, frame_seconds: f32, total_seconds: f32) -> Game {
+    let mut game = Game::with_seed(seed);
+    let frames = (total_seconds / frame_seconds) as usize;
+
+    for _ in 0..frames {
+        autopilot(&mut game);
+        game.step(frame_seconds, Inputs::default());
+    }
+
+    game
+}
+
+#[test]
+fn random_runs_keep_world_consistent() {
+    for seed in 0..1000 {
+        let mut game = Game::with_seed(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        for _ in 0..1200 {
+            game.step(DT, random_inputs(&mut rng));
+
+            assert!(game.world.track.len() <= 100, "seed {}", seed);
+            assert!(
+                game.world.track.contains(game.state.current_rail_idx()),
+                "seed {}",
+                seed
+            );
+            assert!(
+                game.state.current_point_idx
+                    < game.state.get_current_rail_points(&game.world).len(),
+                "seed {}",
+                seed
+            );
+            assert!(game.selected_index < game.lever_count, "seed {}", seed);
+        }
+    }
+}
+
+/// The rail leading into `rail`, found by searching the whole track.
+fn rail_before_by_search(world: &World, rail: RailRef) -> Option<RailRef> {
+    let entry = match world.track[rail.block] {
+        Block::Merge(_) => rail,
+        _ => RailRef {
+            block: rail.block,
+            branch: 0,
+        },
+    };
+
+    world.track.iter().find_map(|(id, block)| {
+        block
+            .rails()
+            .iter()
+            .position(|other| other.next == Some(entry))
+            .map(|branch| RailRef { block: id, branch })
+    })
+}
+
+#[test]
+fn rails_know_the_rail_leading_into_them() {
+    for seed in 0..20 {
+        let mut game = Game::with_seed(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        for _ in 0..1200 {
+            let rails_traversed = game.state.rails_traversed;
+            game.step(DT, random_inputs(&mut rng));
+
+            // The track only grows and gets trimmed as the train enters blocks.
+            if game.state.rails_traversed == rails_traversed {
+                continue;
+            }
+
+            for (id, block) in game.world.track.iter() {
+                for branch in 0..block.rails().len() {
+                    let rail = RailRef { block: id, branch };
+
+                    assert_eq!(
+                        game.world.rail_before(rail),
+                        rail_before_by_search(&game.world, rail),
+                        "seed {}: {:?}",
+                        seed,
+                        rail
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn levers_are_added_as_the_train_speeds_up() {
+    let mut game = Game::with_seed(11).with_levers(LeverSettings::new(2, 4));
+    let mut added = 0;
+
+    assert_eq!(game.lever_count, 2);
+
+    for _ in 0..(90.0 / DT) as usize {
+        autopilot(&mut game);
+        let events = game.step(DT, Inputs::default());
+        added += events
+            .iter()
+            .filter(|&&event| event == GameEvent::LeverAdded)
+            .count();
+
+        assert!(game.state.alive);
+        assert_eq!(game.world.letter_count, game.lever_count);
+        assert!(
+            game.world
+                .track
+                .blocks()
+                .filter_map(Block::letter)
+                .all(|letter| letter.index() < game.lever_count)
+        );
+    }
+
+    assert_eq!(added, 2);
+    assert_eq!(game.lever_count, 4);
+    assert_eq!(game.lever_states().len(), 4);
+}
+
+#[test]
+fn selection_wraps_around_every_lever() {
+    let mut game = Game::with_seed(1).with_levers(LeverSettings::new(5, 5));
+    let left = Inputs {
+        select_left: true,
+        ..Default::default()
+    };
+
+    game.step(DT, left);
+    assert_eq!(game.selected_index, 4);
+    assert_eq!(game.selected_letter(), Letter::E);
+
+    game.step(
+        DT,
+        Inputs {
+            select_right: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(game.selected_index, 0);
+}
+
+#[test]
+fn survival_timer_freezes_on_crash() {
+    let mut game = Game::with_seed(2);
+
+    assert!(run_until_crash(&mut game, 1_000_000));
+
+    let survival_ms = game.state.survival_ms;
+    let points_traversed = game.state.points_traversed;
+
+    for _ in 0..600 {
+        game.step(DT, Inputs::default());
+    }
+
+    assert!(!game.state.alive);
+    assert!(game.state.death_cause.is_some());
+    assert_eq!(game.state.survival_ms, survival_ms);
+    assert_eq!(game.state.points_traversed, points_traversed);
+    assert_eq!(game.state.current_speed(game.ms_to_next_point), 0.0);
+}
+
+#[test]
+fn restart_resets_run() {
+    let mut game = Game::with_seed(3);
+
+    assert!(run_until_crash(&mut game, 1_000_000));
+
+    let events = game.step(
+        DT,
+        Inputs {
+            restart: true,
+            ..Default::default()
+        },
+    );
+
+    assert!(events.contains(&GameEvent::Restarted));
+    assert!(game.state.alive);
+    assert_eq!(game.state.death_cause, None);
+    assert_eq!(game.state.rails_traversed, 0);
+    assert_eq!(game.ms_to_next_point, 100.0);
+    assert!(
+        game.lever_states()
+            .iter()
+            .all(|state| !matches!(state, LeverState::Set { pulled: true, .. }))
+    );
+}
+
+#[test]
+fn restart_is_ignored_while_alive() {
+    let mut game = Game::with_seed(4);
+
+    let events = game.step(
+        DT,
+        Inputs {
+            restart: true,
+            ..Default::default()
+        },
+    );
+
+    assert!(!events.contains(&GameEvent::Restarted));
+}
+
+#[test]
+fn same_seed_generates_same_track() {
+    for seed in 0..100 {
+        let mut a = Game::with_seed(seed);
+        let mut b = Game::with_seed(seed);
+
+        for _ in 0..600 {
+            a.step(DT, Inputs::default());
+            b.step(DT, Inputs::default());
+        }
+
+        assert_eq!(a.world.track, b.world.track);
+    }
+}
+
+#[test]
+fn restart_keeps_fixed_seed() {
+    let mut game = Game::with_seed(42);
+    let mut fresh = Game::with_seed(42);
+    fresh.step(0.0, Inputs::default());
+
+    assert!(run_until_crash(&mut game, 1_000_000));
+
+    game.step(
+        0.0,
+        Inputs {
+            restart: true,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(game.seed(), 42);
+    assert_eq!(game.world.track, fresh.world.track);
+}
+
+#[test]
+fn slow_frames_do_not_slow_down_the_train() {
+    // Frame lengths are powers of two so they add up exactly in `f32`.
+    let mut smooth = run_with_autopilot(7, 1.0 / 128.0, 60.0);
+    let mut choppy = run_with_autopilot(7, 0.25, 60.0);
+
+    // Forks generated during the last frame have not been routed yet.
+    autopilot(&mut smooth);
+    autopilot(&mut choppy);
+
+    assert!(smooth.state.alive && choppy.state.alive);
+    assert_eq!(smooth.state.points_traversed, choppy.state.points_traversed);
+    assert_eq!(smooth.state.rails_traversed, choppy.state.rails_traversed);
+    assert_eq!(
+        smooth.state.current_point_idx,
+        choppy.state.current_point_idx
+    );
+    assert_eq!(smooth.state.survival_ms, choppy.state.survival_ms);
+    assert_eq!(smooth.ms_to_next_point, choppy.ms_to_next_point);
+    assert_eq!(smooth.world.track, choppy.world.track);
+}
+
+#[test]
+fn speed_up_schedule_is_exact() {
+    let game = run_with_autopilot(3, 0.5, 10.0);
+
+    assert_eq!(game.state.survival_ms, 10000.0);
+    // 3000 units at 300 units per second. Curves run a little longer than
+    // the straight line between their points.
+    assert!((95..=100).contains(&game.state.points_traversed));
+    assert_eq!(game.ms_to_next_point, 90.0);
+
+    let game = run_with_autopilot(3, 1.0, 70.0);
+
+    // 10 s each at 100, 90, 80, 70, 60, 50 and 40 ms per point.
+    assert_eq!(game.ms_to_next_point, 30.0);
+}
+
+#[test]
+fn forks_are_fair_at_the_speed_the_train_reaches_them() {
+    let mut forks = 0;
+
+    for seed in 0..10 {
+        let mut game = Game::with_seed(seed);
+        let min_reaction_ms = game.world.presets.min_reaction_ms;
+
+        for _ in 0..(90.0 / DT) as usize {
+            autopilot(&mut game);
+
+            let rails_traversed = game.state.rails_traversed;
+            game.step(DT, Inputs::default());
+
+            let id = game.state.current_rail_idx();
+            let Some(letter) = game.world.track[id].letter() else {
+                continue;
+            };
+
+            if game.state.rails_traversed == rails_traversed {
+                continue;
+            }
+
+            // Points from the start of the last fork with the same letter.
+            let mut points = 0;
+            let mut block = id;
+
+            while let Some(parent) = game.world.parent(block) {
+                points += game.world.rail(parent).points.len() - 1;
+
+                if game.world.track[parent.block].letter() == Some(letter) {
+                    assert!(
+                        points as f32 * game.ms_to_next_point >= min_reaction_ms,
+                        "seed {}: fork {} is only {} points after the last one at {} ms per point",
+                        seed,
+                        id,
+                        points,
+                        game.ms_to_next_point
+                    );
+                    forks += 1;
+                    break;
+                }
+
+                block = parent.block;
+            }
+        }
+    }
+
+    assert!(forks > 50);
+}
+
+#[test]
+fn block_ids_survive_trimming() {
+    for seed in 0..10 {
+        let mut game = Game::with_seed(seed);
+        let mut trimmed = 0;
+
+        for _ in 0..3000 {
+            autopilot(&mut game);
+
+            let before = game.world.track.clone();
+            game.step(DT, Inputs::default());
+
+            for (id, block) in before.iter() {
+                match game.world.track.get(id) {
+                    Some(after) => assert!(
+                        (after.position() - game.origin_shift).distance(block.position()) < 1e-2
+                    ),
+                    None => trimmed += 1,
+                }
+            }
+
+            // New blocks get IDs past every block generated before them.
+            assert!(
+                game.world
+                    .track
+                    .iter()
+                    .all(|(id, _)| before.contains(id) || id >= before.next_id())
+            );
+        }
+
+        assert!(trimmed > 0, "seed {}", seed);
+    }
+}
+
+#[test]
+fn multi_hour_runs_stay_near_the_origin() {
+    const HOURS: usize = 2;
+
+    let mut game = Game::with_seed(8);
+    let mut shifts = 0;
+
+    // Long frames keep the test quick. Track is generated far enough ahead
+    // that the autopilot still sets every fork before the train gets there.
+    for second in 0..HOURS * 3600 {
+        autopilot(&mut game);
+
+        let (before, _) = game.train_transform();
+        let events = game.step(1.0, Inputs::default());
+
+        if events.contains(&GameEvent::OriginShifted) {
+            shifts += 1;
+            assert_ne!(game.origin_shift, Vec2::ZERO);
+        } else {
+            assert_eq!(game.origin_shift, Vec2::ZERO);
+        }
+
+        let (after, _) = game.train_transform();
+
+        assert!(game.state.alive, "crashed after {} s", second);
+        assert!(after.length() < ORIGIN_REBASE_DISTANCE * 1.5);
+        assert!((after - game.origin_shift).distance(before) < 2000.0);
+
+        // Near the origin, points the train runs over keep their spacing.
+        let rail = game.state.get_current_entered_rail(&game.world);
+        let world_points: Vec<Vec2> = rail.world_points().collect();
+
+        for (points, world_points) in rail.points.windows(2).zip(world_points.windows(2)) {
+            let spacing = points[0].distance(points[1]);
+            let world_spacing = world_points[0].distance(world_points[1]);
+            assert!((spacing - world_spacing).abs() < 1e-3);
+        }
+    }
+
+    assert!(shifts > 10);
+}
+
+#[test]
+fn split_branches_diverge_and_unreachable_ones_are_pruned() {
+    let mut splits = 0;
+
+    for seed in 0..50 {
+        let mut game = Game::with_seed(seed);
+
+        for _ in 0..3000 {
+            autopilot(&mut game);
+            game.step(DT, Inputs::default());
+
+            let current_idx = game.state.current_rail_idx();
+            let reachable = game.world.distances_from(current_idx);
+
+            for (id, block) in game.world.track.iter() {
+                assert!(
+                    reachable.contains_key(&id)
+                        || game.state.path.iter().any(|entry| entry.block == id),
+                    "seed {}: block {} can no longer be reached",
+                    seed,
+                    id
+                );
+
+                if let Block::Fork(fork) = block
+                    && reachable.contains_key(&id)
+                {
+                    let mut continuations: Vec<usize> = fork
+                        .branches
+                        .iter()
+                        .filter_map(|rail| rail.next)
+                        .map(|next| next.block)
+                        .collect();
+                    // Bypasses lead both branches into the same merge.
+                    continuations.dedup();
+
+                    if continuations.len() > 1 {
+                        splits += 1;
+
+                        let start = |next: usize| game.world.track[next].position();
+                        assert_ne!(start(continuations[0]), start(continuations[1]));
+                    }
+                }
+            }
+        }
+    }
+
+    assert!(splits > 0);
+}
+
+const BYPASS_PRESETS: &str = r#"
+[rails.straight]
+spacing = 30.0
+points = 8
+angle_step_degrees = 0.0
+
+[rails.spur]
+spacing = 30.0
+points = 5
+angle_step_degrees = 15.0
+
+[[preset]]
+name = "straight"
+weight = 0.0
+blocks = [{ rail = "straight" }]
+
+[[preset]]
+name = "bypass"
+weight = 1.0
+blocks = [{ merge = "spur", mirror = "random", gate = "random" }, { rail = "straight" }]
+"#;
+
+fn bypass_game(seed: u64) -> Game {
+    Game::with_seed(seed).with_presets(Rc::new(PresetLibrary::parse(BYPASS_PRESETS).unwrap()))
+}
+
+#[test]
+fn closed_gates_stop_the_spur() {
+    for seed in 0..20 {
+        // Forks start on the spur, and gates start closed.
+        let mut game = bypass_game(seed);
+
+        assert!(run_until_crash(&mut game, 10_000));
+
+        let entry = *game.state.path.last().unwrap();
+        assert_eq!(entry.branch, 1);
+        assert_eq!(
+            game.state.death_cause,
+            Some(DeathCause::Hazard(Hazard::ClosedGate))
+        );
+        assert!(matches!(
+            &game.world.track[entry.block],
+            Block::Merge(merge) if merge.gate.is_some_and(|gate| !gate.open)
+        ));
+    }
+}
+
+#[test]
+fn open_gates_let_the_spur_rejoin_the_track() {
+    for seed in 0..20 {
+        let mut game = bypass_game(seed);
+        let mut spur_merges = 0;
+
+        for _ in 0..3000 {
+            autopilot(&mut game);
+            game.step(DT, Inputs::default());
+
+            let entry = *game.state.path.last().unwrap();
+
+            if entry.branch == 1 && game.state.current_point_idx == 0 {
+                spur_merges += 1;
+            }
+        }
+
+        assert!(game.state.alive);
+        assert!(spur_merges > 0);
+
+        // The spur ends where the main line does.
+        for block in &game.world.track {
+            if let Block::Merge(merge) = block {
+                let rail_end = merge.rail.world_points().last().unwrap();
+                let spur_end = merge.spur.world_points().last().unwrap();
+                assert!(rail_end.distance(spur_end) < 1e-3);
+            }
+        }
+    }
+}
+
+const LETTER_A_PRESETS: &str = r#"
+[rails.straight]
+spacing = 30.0
+points = 8
+angle_step_degrees = 0.0
+
+[rails.u_turn]
+spacing = 30.0
+points = 5
+angle_step_degrees = 22.5
+
+[[preset]]
+name = "straight"
+weight = 1.0
+blocks = [{ rail = "straight" }]
+
+[[preset]]
+name = "fork"
+weight = 1.0
+blocks = [
+    { fork = [{ rail = "u_turn" }, { rail = "u_turn", mirrored = true }], walls = 1, letter = "A" },
+    { rail = "straight" },
+]
+"#;
+
+fn letter_a_game(seed: u64) -> Game {
+    Game::with_seed(seed).with_presets(Rc::new(PresetLibrary::parse(LETTER_A_PRESETS).unwrap()))
+}
+
+#[test]
+fn train_runs_smoothly_at_its_speed() {
+    for seed in 0..5 {
+        // Bypasses meet the main line at a sharp corner, which no curve
+        // through the track points can round off.
+        let mut game = letter_a_game(seed);
+
+        for _ in 0..3000 {
+            autopilot(&mut game);
+
+            let slowest = game.state.current_speed(game.ms_to_next_point) * DT;
+            let (before, heading_before) = game.train_transform();
+            game.step(DT, Inputs::default());
+            let (after, heading_after) = game.train_transform();
+            let after = after - game.origin_shift;
+            let fastest = game.state.current_speed(game.ms_to_next_point) * DT;
+
+            // Cutting across a curve is a little shorter than running along it.
+            let moved = before.distance(after);
+            assert!(moved <= fastest + 1e-2, "seed {}: moved {}", seed, moved);
+            assert!(moved >= slowest * 0.95, "seed {}: moved {}", seed, moved);
+
+            let turn =
+                Vec2::from_angle(heading_before).angle_between(Vec2::from_angle(heading_after));
+            // Well below the 22.5 degrees a u-turn bends at each of its points.
+            assert!(turn.abs() < 0.25, "seed {}: turned {}", seed, turn);
+        }
+
+        assert!(game.state.alive);
+    }
+}
+
+#[test]
+fn untouched_forks_end_in_walls() {
+    for seed in 0..10 {
+        let mut game = letter_a_game(seed);
+
+        assert!(run_until_crash(&mut game, 100_000));
+        assert_eq!(game.state.death_cause, Some(DeathCause::Wall));
+        assert!(game.state.current_rail_is_blocked(&game.world));
+    }
+}
+
+#[test]
+fn upcoming_forks_match_lever_targets() {
+    for seed in 0..10 {
+        let mut game = Game::with_seed(seed);
+        let mut listed = 0;
+
+        for _ in 0..3000 {
+            autopilot(&mut game);
+            game.step(DT, Inputs::default());
+
+            let upcoming = game.upcoming_forks(5);
+            listed += upcoming.len();
+
+            assert!(upcoming.len() <= 5);
+            assert!(
+                upcoming
+                    .windows(2)
+                    .all(|pair| pair[0].distance < pair[1].distance)
+            );
+
+            for fork in &upcoming {
+                let block = &game.world.track[fork.block];
+
+                assert_eq!(block.letter(), Some(fork.letter));
+                assert_eq!(block.is_set_to_wall(), fork.to_wall);
+                assert_eq!(
+                    fork.targeted,
+                    game.next_fork_index(fork.letter) == Some(fork.block)
+                );
+            }
+        }
+
+        assert!(listed > 0);
+    }
+}
+
+#[test]
+fn upcoming_fork_distance_counts_down_to_the_fork() {
+    let mut game = letter_a_game(3);
+    let first = game.upcoming_forks(1)[0];
+    let points_traversed = game.state.points_traversed;
+
+    while game.state.current_rail_idx() != first.block {
+        game.step(DT, Inputs::default());
+    }
+
+    assert_eq!(
+        (game.state.points_traversed - points_traversed) as usize,
+        first.distance
+    );
+}
+
+#[test]
+fn levers_without_a_fork_ahead_do_not_move() {
+    let mut game = letter_a_game(1);
+    let rails = game.world.track.clone();
+
+    assert_eq!(game.lever_state(Letter::B), LeverState::NoForkAhead);
+
+    let events = game.step(
+        DT,
+        Inputs {
+            select_right: true,
+            ..Default::default()
+        },
+    );
+    assert!(events.contains(&GameEvent::SelectorMoved));
+
+    let events = game.step(
+        DT,
+        Inputs {
+            pull_lever: true,
+            ..Default::default()
+        },
+    );
+
+    assert!(!events.contains(&GameEvent::LeverPulled));
+    assert_eq!(game.lever_state(Letter::B), LeverState::NoForkAhead);
+    assert_eq!(game.world.track, rails);
+}
+
+#[test]
+fn levers_follow_the_next_fork_with_their_letter() {
+    for seed in 0..20 {
+        let mut game = letter_a_game(seed);
+        let pull = Inputs {
+            pull_lever: true,
+            ..Default::default()
+        };
+
+        let LeverState::Set { pulled, to_wall } = game.lever_state(Letter::A) else {
+            panic!("seed {}: the track ahead has a fork", seed);
+        };
+        assert!(!pulled);
+
+        // Two-branch forks have exactly one wall, so a pull flips the wall.
+        game.step(DT, pull);
+        assert_eq!(
+            game.lever_state(Letter::A),
+            LeverState::Set {
+                pulled: true,
+                to_wall: !to_wall
+            }
+        );
+
+        if !to_wall {
+            game.step(DT, pull);
+        }
+
+        let forks_passed = game.state.forks_passed;
+
+        while game.state.forks_passed == forks_passed {
+            game.step(DT, Inputs::default());
+            assert!(game.state.alive, "seed {}", seed);
+        }
+
+        // The lever now shows the fork after the one the train took.
+        assert!(matches!(
+            game.lever_state(Letter::A),
+            LeverState::Set { pulled: false, .. }
+        ));
+    }
+}
+
+const PULL: Inputs = Inputs {
+    select_left: false,
+    select_right: false,
+    pull_lever: true,
+    restart: false,
+};
+
+fn with_targeting(game: Game, targeting: LeverTargeting) -> Game {
+    game.with_levers(LeverSettings::default().with_targeting(targeting))
+}
+
+#[test]
+fn all_targeting_switches_every_visible_fork_of_the_letter() {
+    for seed in 0..10 {
+        let mut game = with_targeting(letter_a_game(seed), LeverTargeting::All);
+
+        while game.lever_targets(Letter::A).is_empty() {
+            game.step(DT, Inputs::default());
+        }
+
+        let targets = game.lever_targets(Letter::A);
+        let distances = game.world.distances_from(game.state.current_rail_idx());
+
+        assert!(targets.iter().all(|id| {
+            distances
+                .get(id)
+                .is_some_and(|&distance| distance < VISIBLE_BLOCKS)
+        }));
+
+        let rails = game.world.track.clone();
+        game.step(DT, PULL);
+
+        for (id, block) in game.world.track.iter() {
+            assert_eq!(block.is_switched(), targets.contains(&id));
+            assert_eq!(block == &rails[id], !targets.contains(&id));
+        }
+    }
+}
+
+#[test]
+fn nearest_targeting_ignores_the_letter() {
+    for seed in 0..10 {
+        let mut game = with_targeting(Game::with_seed(seed), LeverTargeting::Nearest);
+        let nearest = game.upcoming_forks(1)[0];
+
+        for letter in Letter::first(game.lever_count) {
+            assert_eq!(game.lever_targets(letter), vec![nearest.block]);
+        }
+
+        // Whatever lever is selected, the nearest fork is switched.
+        game.step(
+            DT,
+            Inputs {
+                select_left: true,
+                pull_lever: true,
+                ..Default::default()
+            },
+        );
+
+        assert!(game.world.track[nearest.block].is_switched());
+        assert_eq!(
+            game.world
+                .track
+                .blocks()
+                .filter(|block| block.is_switched())
+                .count(),
+            1
+        );
+    }
+}
+
+#[test]
+fn sticky_forks_follow_their_lever() {
+    for seed in 0..10 {
+        let mut game = with_targeting(letter_a_game(seed), LeverTargeting::Sticky);
+
+        game.step(DT, PULL);
+
+        let mut checked = 0;
+
+        while game.state.alive && game.state.rails_traversed < 10 {
+            // Forks generated after the pull follow the lever too.
+            for idx in game.lever_targets(Letter::A) {
+                assert!(game.world.track[idx].is_switched(), "seed {}", seed);
+                checked += 1;
+            }
+
+            game.step(DT, Inputs::default());
+        }
+
+        assert!(checked > 0);
+        assert_eq!(game.lever_pulls[0], 1);
+    }
+}
+
+#[test]
+fn cars_follow_the_locomotive() {
+    let pitch = CAR_LENGTH + CAR_GAP;
+
+    for seed in 0..5 {
+        let mut game = Game::with_seed(seed).with_train(TrainSettings::new(6, 6));
+
+        for _ in 0..3000 {
+            autopilot(&mut game);
+            game.step(DT, Inputs::default());
+
+            let (locomotive, _) = game.train_transform();
+            let cars = game.car_transforms();
+            assert_eq!(cars.len(), 6);
+
+            let first_gap = locomotive.distance(cars[0].0);
+            assert!(first_gap <= (LOCOMOTIVE_LENGTH + CAR_LENGTH) / 2.0 + CAR_GAP + 1e-3);
+            assert!(first_gap > 0.5 * pitch);
+
+            // Bends cut corners, so cars are never further apart than along
+            // the track.
+            for pair in cars.windows(2) {
+                let gap = pair[0].0.distance(pair[1].0);
+                assert!(gap <= pitch + 1e-3 && gap > 0.5 * pitch, "seed {}", seed);
+            }
+
+            // Past the first few frames, every car is on the trail.
+            if game.state.points_traversed > 40 {
+                for (car, _) in &cars {
+                    assert!(
+                        game.state
+                            .trail
+                            .iter()
+                            .any(|point| point.distance(*car) <= 30.0)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn cars_stay_put_when_forks_under_them_switch() {
+    let mut game = Game::with_seed(2).with_train(TrainSettings::new(8, 8));
+
+    for _ in 0..1200 {
+        autopilot(&mut game);
+        game.step(DT, Inputs::default());
+    }
+
+    let cars = game.car_transforms();
+    let current_idx = game.state.current_rail_idx();
+
+    for (id, block) in game.world.track.iter_mut() {
+        if id != current_idx {
+            block.switch();
+        }
+    }
+
+    assert_eq!(game.car_transforms(), cars);
+}
+
+#[test]
+fn train_grows_with_score() {
+    let mut game = Game::with_seed(4).with_train(TrainSettings::new(1, 3));
+    let mut longest = 0;
+
+    assert_eq!(game.car_count(), 1);
+
+    for _ in 0..(120.0 / DT) as usize {
+        autopilot(&mut game);
+        game.step(DT, Inputs::default());
+
+        assert!(game.car_count() >= longest);
+        longest = game.car_count();
+    }
+
+    assert!(game.state.alive);
+    assert_eq!(game.car_count(), 3);
+}
+
+const LOOP_PRESETS: &str = r#"
+[rails.straight]
+spacing = 30.0
+points = 8
+angle_step_degrees = 0.0
+
+[rails.short_straight]
+spacing = 30.0
+points = 5
+angle_step_degrees = 0.0
+
+[rails.curl]
+spacing = 30.0
+points = 13
+angle_step_degrees = 22.5
+
+[[preset]]
+name = "straight"
+weight = 0.0
+blocks = [{ rail = "straight" }]
+
+[[preset]]
+name = "loop"
+weight = 1.0
+blocks = [
+    { rail = "short_straight" },
+    { rail = "curl", mirror = "random" },
+    { rail = "straight" },
+]
+"#;
+
+fn loop_game(seed: u64, cars: usize) -> Game {
+    Game::with_seed(seed)
+        .with_presets(Rc::new(PresetLibrary::parse(LOOP_PRESETS).unwrap()))
+        .with_train(TrainSettings::new(cars, cars))
+}
+
+#[test]
+fn long_trains_crash_on_their_own_crossings() {
+    for seed in 0..10 {
+        let mut game = loop_game(seed, 10);
+
+        assert!(!game.crossings.is_empty());
+        assert!(run_until_crash(&mut game, 10_000), "seed {}", seed);
+        assert!(!game.state.current_rail_is_blocked(&game.world));
+        assert_eq!(
+            game.state.death_cause,
+            Some(DeathCause::Hazard(Hazard::OwnTrain))
+        );
+    }
+}
+
+#[test]
+fn short_trains_pass_their_own_crossings() {
+    for seed in 0..10 {
+        let mut game = loop_game(seed, 6);
+        let mut crossings_passed = 0;
+
+        for _ in 0..3000 {
+            let (before, _) = game.train_transform();
+            game.step(DT, Inputs::default());
+            let (after, _) = game.train_transform();
+
+            crossings_passed += game
+                .crossings
+                .iter()
+                .filter(|crossing| {
+                    let along = crossing.position - before;
+                    let step = after - before;
+                    along.perp_dot(step).abs() < 1.0
+                        && (0.0..step.length_squared()).contains(&along.dot(step))
+                })
+                .count();
+        }
+
+        assert!(game.state.alive, "seed {}", seed);
+        assert!(crossings_passed > 0);
+    }
+}
+
+/// Every crossing of the track, found from scratch on the current curves.
+fn all_crossings(world: &World) -> Vec<Crossing> {
+    let curves: Vec<_> = world
+        .track
+        .iter()
+        .map(|(id, _)| (id, world.block_curves(id)))
+        .collect();
+
+    let mut crossings = Vec::new();
+
+    for (i, (a, curves_a)) in curves.iter().enumerate() {
+        for (b, curves_b) in &curves[i..] {
+            crossings.extend(curve_crossings(*a, curves_a, *b, curves_b));
+        }
+    }
+
+    crossings
+}
+
+#[test]
+fn crossings_follow_the_track_as_it_changes() {
+    let mut checked = 0;
+
+    for seed in 0..5 {
+        // Loops cross themselves, and forks bend the rails leading into them.
+        // Forks are only switched through levers, which the crossings follow.
+        for mut game in [loop_game(seed, 0), Game::with_seed(seed)] {
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            for _ in 0..3000 {
+                let rails_traversed = game.state.rails_traversed;
+                let crossings = game.crossings.len();
+
+                let events = game.step(DT, random_inputs(&mut rng));
+
+                if game.state.rails_traversed == rails_traversed
+                    && !events.contains(&GameEvent::LeverPulled)
+                {
+                    continue;
+                }
+
+                for (id, _) in game.world.track.iter() {
+                    assert_eq!(
+                        game.rail_curves[&id],
+                        game.world.block_curves(id),
+                        "seed {}: block {}",
+                        seed,
+                        id
+                    );
+                }
+
+                let expected = all_crossings(&game.world);
+                assert_eq!(game.crossings.len(), expected.len(), "seed {}", seed);
+
+                for crossing in &expected {
+                    let mut blocks = crossing.blocks;
+                    blocks.sort();
+
+                    assert!(
+                        game.crossings.iter().any(|found| {
+                            let mut found_blocks = found.blocks;
+                            found_blocks.sort();
+
+                            found_blocks == blocks
+                                && found.position.distance(crossing.position) < 1e-3
+                        }),
+                        "seed {}: missing {:?} after {} crossings",
+                        seed,
+                        crossing,
+                        crossings
+                    );
+                }
+
+                checked += expected.len();
+            }
+        }
+    }
+
+    assert!(checked > 0);
+}
@@ -0,0 +1,59 @@
+use d2j2025::{Block, Rail, track::Track};
+use macroquad::math::vec2;
+
+fn straight(x: f32) -> Block {
+    Block::Rail(Rail::new_straight(vec2(x, 0.0), 0.0, false, 30.0, 8))
+}
+
+#[test]
+fn ids_stay_put_when_blocks_are_removed() {
+    let mut track = Track::new();
+    let ids: Vec<usize> = (0..5).map(|i| track.push(straight(i as f32))).collect();
+
+    assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+
+    track.remove(2);
+    track.remove(0);
+
+    assert_eq!(track.len(), 3);
+    assert!(!track.contains(0) && !track.contains(2));
+    assert_eq!(track[3], straight(3.0));
+    assert_eq!(
+        track.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+        vec![1, 3, 4]
+    );
+
+    // IDs of removed blocks are never handed out again.
+    assert_eq!(track.push(straight(5.0)), 5);
+
+    track.remove(1);
+    assert_eq!(track.get(3), Some(&straight(3.0)));
+    assert_eq!(track.next_id(), 6);
+}
+
+#[test]
+fn truncate_does_not_hand_ids_out_again() {
+    let mut track = Track::new();
+
+    for i in 0..4 {
+        track.push(straight(i as f32));
+    }
+
+    track.remove(0);
+    track.truncate(2);
+
+    assert_eq!(track.len(), 1);
+    assert_eq!(track.next_id(), 4);
+    assert_eq!(track.get(2), None);
+    assert_eq!(track.push(straight(9.0)), 4);
+    assert_eq!(track[4], straight(9.0));
+    assert_eq!(
+        track.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+        vec![1, 4]
+    );
+
+    // Truncating everything still keeps the IDs going.
+    track.truncate(0);
+    assert!(track.is_empty());
+    assert_eq!(track.push(straight(10.0)), 5);
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use d2j2025::{
+    Block,
+    game::{Game, GameEvent, Inputs, LeverSettings, LeverTargeting},
+    track_art::{
+        BLADE_COLOR, BUFFER_STOP_COLOR, GAUGE, MAX_MESH_INDICES, TrackArt, block_art, offset_curve,
+        points_along,
+    },
+};
+use macroquad::math::{Rect, Vec2, vec2};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+#[test]
+fn rails_stay_a_gauge_apart_around_curves() {
+    let curve: Vec<Vec2> = (0..=16)
+        .map(|i| 100.0 * Vec2::from_angle(i as f32 * 0.1))
+        .collect();
+
+    let left = offset_curve(&curve, GAUGE / 2.0);
+    let right = offset_curve(&curve, -GAUGE / 2.0);
+
+    for (left, right) in left.iter().zip(&right) {
+        assert!((left.distance(*right) - GAUGE).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn sleepers_are_evenly_spaced() {
+    let curve = [vec2(0.0, 0.0), vec2(40.0, 0.0), vec2(40.0, 60.0)];
+    let sleepers = points_along(&curve, 10.0);
+
+    assert_eq!(sleepers.len(), 10);
+    assert_eq!(sleepers[0], (vec2(5.0, 0.0), Vec2::X));
+    assert_eq!(sleepers[4], (vec2(40.0, 5.0), Vec2::Y));
+}
+
+#[test]
+fn forks_get_switch_blades_and_walls_buffer_stops() {
+    let game = Game::with_seed(0);
+    // Rails the train would not take are faded, so only hues are compared.
+    let has_color = |id, color| {
+        block_art(&game.world, id)
+            .triangles
+            .iter()
+            .any(|triangle| triangle.color.with_alpha(1.0) == color)
+    };
+
+    let mut forks = 0;
+
+    for (id, block) in game.world.track.iter() {
+        // Closed gates are red like buffer stops.
+        let stops = block.rails().iter().any(|rail| rail.is_wall)
+            || matches!(block, Block::Merge(merge) if merge.gate.is_some_and(|gate| !gate.open));
+
+        assert_eq!(has_color(id, BUFFER_STOP_COLOR), stops, "block {}", id);
+        assert_eq!(
+            has_color(id, BLADE_COLOR),
+            matches!(block, Block::Fork(_)),
+            "block {}",
+            id
+        );
+
+        if let Block::Fork(_) = block {
+            forks += 1;
+        }
+    }
+
+    assert!(forks > 0);
+}
+
+#[test]
+fn every_block_fits_in_its_meshes() {
+    for seed in 0..5 {
+        let mut game = Game::with_seed(seed);
+
+        for _ in 0..60 * 60 {
+            // Keep every fork on an open branch, so the run goes on.
+            for block in &mut game.world.track {
+                if let Block::Fork(fork) = block {
+                    while fork.selected_rail().is_wall {
+                        fork.cycle();
+                    }
+                }
+            }
+
+            game.step(1.0 / 60.0, Inputs::default());
+        }
+
+        for (id, _) in game.world.track.iter() {
+            let art = block_art(&game.world, id);
+            let meshes = art.to_meshes();
+            // Corners on the far edges may round to just outside.
+            let bounds = art.bounds();
+            let bounds = Rect::new(
+                bounds.x - 1e-2,
+                bounds.y - 1e-2,
+                bounds.w + 2e-2,
+                bounds.h + 2e-2,
+            );
+
+            // Generated blocks are small enough to draw in a single call.
+            assert_eq!(meshes.len(), 1, "block {}", id);
+            assert!(meshes[0].indices.len() <= MAX_MESH_INDICES, "block {}", id);
+            assert_eq!(meshes[0].vertices.len(), art.triangles.len() * 3);
+            assert!(
+                art.triangles
+                    .iter()
+                    .flat_map(|triangle| triangle.corners)
+                    .all(|corner| bounds.contains(corner))
+            );
+        }
+    }
+}
+
+#[test]
+fn switching_a_fork_changes_only_nearby_art() {
+    let mut game = Game::with_seed(0);
+    let before: Vec<_> = game
+        .world
+        .track
+        .iter()
+        .map(|(id, _)| block_art(&game.world, id))
+        .collect();
+
+    let (fork_id, _) = game
+        .world
+        .track
+        .iter()
+        .find(|(_, block)| matches!(block, Block::Fork(_)))
+        .unwrap();
+
+    if let Block::Fork(fork) = &mut game.world.track[fork_id] {
+        fork.cycle();
+    }
+
+    let changed: Vec<_> = game
+        .world
+        .track
+        .iter()
+        .zip(&before)
+        .filter(|((id, _), art)| block_art(&game.world, *id) != **art)
+        .map(|((id, _), _)| id)
+        .collect();
+
+    // The rail leading into the fork bends towards the selected branch.
+    assert!(changed.contains(&fork_id));
+    assert!(
+        changed
+            .iter()
+            .all(|id| *id + 1 >= fork_id && *id <= fork_id)
+    );
+}
+
+#[test]
+fn long_rails_are_split_over_several_meshes() {
+    // Around 1700 sleepers, two triangles each.
+    let curve = [vec2(0.0, 0.0), vec2(20000.0, 0.0)];
+    let mut art = TrackArt::new();
+    art.sleepers(&curve, 1.0);
+
+    let meshes = art.to_meshes();
+
+    assert!(meshes.len() >= 3);
+    assert_eq!(
+        meshes.iter().map(|mesh| mesh.vertices.len()).sum::<usize>(),
+        art.triangles.len() * 3
+    );
+
+    for mesh in &meshes {
+        assert!(mesh.indices.len() <= MAX_MESH_INDICES);
+        assert!(
+            mesh.indices
+                .iter()
+                .all(|&index| (index as usize) < mesh.vertices.len())
+        );
+    }
+}
+
+#[test]
+fn changed_blocks_cover_every_change_in_art() {
+    let sticky = LeverSettings::default().with_targeting(LeverTargeting::Sticky);
+
+    for seed in 0..3 {
+        for mut game in [
+            Game::with_seed(seed),
+            Game::with_seed(seed).with_levers(sticky),
+        ] {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut arts: HashMap<_, _> = game
+                .world
+                .track
+                .iter()
+                .map(|(id, _)| (id, block_art(&game.world, id)))
+                .collect();
+
+            for _ in 0..1500 {
+                let inputs = Inputs {
+                    select_left: rng.random_bool(0.05),
+                    select_right: rng.random_bool(0.05),
+                    pull_lever: rng.random_bool(0.05),
+                    restart: true,
+                };
+                let rails_traversed = game.state.rails_traversed;
+                let events = game.step(1.0 / 60.0, inputs);
+
+                // The track only changes when the train enters a block, or
+                // when a lever is pulled.
+                if game.state.rails_traversed == rails_traversed
+                    && !events.contains(&GameEvent::LeverPulled)
+                    && !events.contains(&GameEvent::Restarted)
+                {
+                    continue;
+                }
+
+                // Shifted art is rebuilt here, to compare it exactly.
+                let rebuilt = events.contains(&GameEvent::Restarted)
+                    || events.contains(&GameEvent::OriginShifted);
+
+                if rebuilt {
+                    arts.clear();
+                }
+
+                arts.retain(|id, _| game.world.track.contains(*id));
+
+                for (id, _) in game.world.track.iter() {
+                    let art = block_art(&game.world, id);
+
+                    // New blocks count as changed too.
+                    if !rebuilt && arts.get(&id) != Some(&art) {
+                        assert!(
+                            game.changed_blocks.contains(&id),
+                            "seed {}: block {}",
+                            seed,
+                            id
+                        );
+                    }
+
+                    arts.insert(id, art);
+                }
+            }
+        }
+    }
+}